@@ -0,0 +1,154 @@
+//! Kumo's command line.
+//!
+//! Without a subcommand, Kumo starts, or shows the window of the running instance.
+//! A deviantart.com URL can be given instead of a subcommand, to open it.
+//! Subcommands are sent to the running instance. Those that need the interface
+//! start an instance first if none is running.
+
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Runs Kumo, or controls the running instance.
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct Cli {
+    /// A deviantart.com URL to open, like `kumo open`.
+    #[arg(value_name = "URL", value_parser = link)]
+    pub url: Option<String>,
+
+    /// Runs without a window, e.g. on a server.
+    /// Meilisearch, DeviantArt and IPC keep working.
+    #[arg(long)]
+    pub headless: bool,
+
+    /// The directory Kumo loads its assets from.
+    #[arg(long, value_name = "DIR", value_parser = existing_dir)]
+    pub assets: Option<PathBuf>,
+
+    /// The directory containing Meilisearch and its database.
+    /// Defaults to the executable's directory.
+    #[arg(long, value_name = "DIR", value_parser = existing_dir)]
+    pub meili: Option<PathBuf>,
+
+    /// The port used for single instancing and IPC, if Kumo can't use a Unix socket.
+    #[arg(long, value_name = "PORT", value_parser = clap::value_parser!(u16).range(1..))]
+    pub instance_port: Option<u16>,
+
+    /// The port used for DeviantArt's OAuth2 redirect.
+    #[arg(long, value_name = "PORT", value_parser = clap::value_parser!(u16).range(1..))]
+    pub oauth_port: Option<u16>,
+
+    /// The port used by the Meilisearch server.
+    #[arg(long, value_name = "PORT", value_parser = clap::value_parser!(u16).range(1..))]
+    pub meili_port: Option<u16>,
+
+    /// Overrides DeviantArt's OAuth2 authorization URL, e.g. for a mock server.
+    #[arg(long, value_name = "URL", value_parser = url, hide_short_help = true)]
+    pub deviantart_auth_url: Option<String>,
+
+    /// Overrides DeviantArt's OAuth2 token URL.
+    #[arg(long, value_name = "URL", value_parser = url, hide_short_help = true)]
+    pub deviantart_token_url: Option<String>,
+
+    /// Overrides the OAuth2 redirect URL, which defaults to localhost on the OAuth2 port.
+    #[arg(long, value_name = "URL", value_parser = redirect_url, hide_short_help = true)]
+    pub deviantart_redirect_url: Option<String>,
+
+    /// Overrides DeviantArt's API URL.
+    #[arg(long, value_name = "URL", value_parser = url, hide_short_help = true)]
+    pub deviantart_api_url: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Shows the window of the running instance.
+    Show,
+    /// Hides the window of the running instance.
+    Hide,
+    /// Shows the window of the running instance if it's hidden, hides it otherwise.
+    /// Bind this to a shortcut where Kumo's global hotkey isn't available.
+    Toggle,
+    /// Searches for the query.
+    Search { query: String },
+    /// Opens a deviantart.com deviation, gallery, favourites or user URL.
+    Open {
+        #[arg(value_parser = link)]
+        url: String,
+    },
+    /// Authenticates with DeviantArt.
+    Auth,
+    /// Exits the running instance.
+    Quit,
+    /// Reports the status of the running instance.
+    Status,
+}
+
+impl Cli {
+    /// Returns the given ports, overridden by those on the command line.
+    pub fn ports(&self, configured: settings::Ports) -> settings::Ports {
+        settings::Ports {
+            instance: self.instance_port.unwrap_or(configured.instance),
+            oauth: self.oauth_port.unwrap_or(configured.oauth),
+            meilisearch: self.meili_port.unwrap_or(configured.meilisearch),
+        }
+    }
+
+    /// Returns the configured endpoints, overridden by those on the command line.
+    pub fn endpoints(
+        &self,
+        ports: &settings::Ports,
+        configured: &settings::DeviantArtEndpoints,
+    ) -> deviantart::Endpoints {
+        let default = deviantart::Endpoints::configured(ports, configured);
+        let or = |url: &Option<String>, default: String| url.clone().unwrap_or(default);
+        deviantart::Endpoints {
+            auth_url: or(&self.deviantart_auth_url, default.auth_url),
+            token_url: or(&self.deviantart_token_url, default.token_url),
+            redirect_url: or(&self.deviantart_redirect_url, default.redirect_url),
+            api_url: or(&self.deviantart_api_url, default.api_url),
+        }
+    }
+
+    /// Returns the request for the running instance.
+    pub fn request(&self) -> ipc::Request {
+        if let Some(url) = &self.url {
+            return ipc::Request::Open { url: url.clone() };
+        }
+        match self.command.clone().unwrap_or(Command::Show) {
+            Command::Show => ipc::Request::Show,
+            Command::Hide => ipc::Request::Hide,
+            Command::Toggle => ipc::Request::Toggle,
+            Command::Search { query } => ipc::Request::Search { query },
+            Command::Open { url } => ipc::Request::Open { url },
+            Command::Auth => ipc::Request::Authenticate,
+            Command::Quit => ipc::Request::Quit,
+            Command::Status => ipc::Request::Status,
+        }
+    }
+}
+
+fn existing_dir(value: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+    if path.is_dir() {
+        Ok(path)
+    } else {
+        Err(format!("`{value}` is not a directory"))
+    }
+}
+
+fn url(value: &str) -> Result<String, String> {
+    deviantart::Endpoints::check_url(value).map(|()| value.to_owned())
+}
+
+fn redirect_url(value: &str) -> Result<String, String> {
+    deviantart::Endpoints::check_redirect_url(value).map(|()| value.to_owned())
+}
+
+fn link(value: &str) -> Result<String, String> {
+    deviantart::Link::parse(value)
+        .map(|_| value.to_owned())
+        .map_err(|e| e.to_string())
+}
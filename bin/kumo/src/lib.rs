@@ -1,6 +1,11 @@
 use bevy::prelude::*;
 
-pub fn build() -> App {
+pub mod cli;
+
+/// How long a headless Kumo waits between updates.
+const HEADLESS_WAIT: std::time::Duration = std::time::Duration::from_millis(100);
+
+pub fn build(cli: &cli::Cli) -> App {
     let mut app = App::new();
 
     app.add_plugin(TaskPoolPlugin::default());
@@ -14,19 +19,54 @@ pub fn build() -> App {
 
     app.add_plugin(AssetPlugin {
         watch_for_changes: true,
-        asset_folder: std::env::args()
-            .find(|s| s.starts_with("--assets="))
-            .map(|s| s.split_once('=').unwrap().1.to_owned())
-            .unwrap_or(Default::default()),
+        asset_folder: cli.assets.as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default(),
     });
 
-    app.add_plugin(window::WindowPlugin);
+    // Settings come first, since other plugins read them.
+    app.add_plugin(settings::SettingsPlugin);
+    let ports = cli.ports(*app.world.resource::<settings::Ports>());
+    app.insert_resource(ports);
+
+    if cli.headless {
+        // Without winit, something else has to drive the app.
+        // This is as often as winit updates a focused window, so IPC stays responsive.
+        app.insert_resource(bevy::app::ScheduleRunnerSettings::run_loop(HEADLESS_WAIT));
+        app.add_plugin(bevy::app::ScheduleRunnerPlugin);
+    } else {
+        app.add_plugin(theme::ThemePlugin);
+        app.add_plugin(window::WindowPlugin);
+        app.add_plugin(hotkey::HotkeyPlugin);
+    }
+
+    // Requests from other processes that aren't handled by the instance itself
+    // are forwarded as events, see main.rs.
+    app.add_event::<ipc::Request>();
 
-    app.add_plugin(meiliguard::MeilisearchPlugin);
+    app.add_plugin(meiliguard::MeilisearchPlugin {
+        meili_dir: cli.meili.clone(),
+    });
 
-    app.add_plugin(interface::InterfacePlugin);
+    if !cli.headless {
+        app.add_plugin(interface::InterfacePlugin);
+    }
 
-    app.add_plugin(deviantart::DeviantArtPlugin);
+    let configured = app.world.resource::<settings::Settings>().deviantart.endpoints.clone();
+    app.add_plugin(deviantart::DeviantArtPlugin {
+        endpoints: cli.endpoints(&ports, &configured),
+    });
+    app.add_system(authenticate_system);
 
     app
 }
+
+/// Authentication requested by another process.
+fn authenticate_system(
+    mut requests: EventReader<ipc::Request>,
+    mut authenticate: EventWriter<deviantart::Authenticate>,
+) {
+    if requests.iter().any(|request| *request == ipc::Request::Authenticate) {
+        authenticate.send(deviantart::Authenticate);
+    }
+}
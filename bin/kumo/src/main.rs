@@ -1,28 +1,87 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 //! ---== PORT USAGE ==---
-//! 11210 is used to achieve single instancing, and inter-process communication.
+//! 11210 is used to achieve single instancing, and inter-process communication (see the `ipc` crate),
+//! unless Kumo can use a Unix socket in `$XDG_RUNTIME_DIR` instead.
 //! 11211 is used for DeviantArt's OAuth2 process.
 //! 11212 is used by the Meilisearch server.
-//! Configurability for these ports is planned, but not a priority.
-//! If any of these ports shouldn't work for you, feel free to change the source code!
+//! These are the defaults; they can be changed in the settings file,
+//! or with `--instance-port`, `--oauth-port` and `--meili-port`.
 
 use bevy::prelude::*;
+use clap::Parser;
+
+/// How long we wait for the running instance to answer.
+const IPC_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How long the running instance waits for a request on an accepted connection.
+/// Reading blocks the main thread, and clients send their request right after connecting,
+/// so connections that stay idle are dropped quickly instead of stalling the interface.
+const IPC_READ_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(50);
 
 fn main() {
+    let cli = kumo::cli::Cli::parse();
+    let request = cli.request();
+    let port = cli.ports(settings::Ports::load()).instance;
+
     // The first thing we do is check for another instance.
     // For Kumo it is sensible to not have multiple instances running simultaneously.
-    // Instead of using `single_instance` for this, Kumo uses a TCP socket.
-    // So we first check if our socket is already occupied:
-    if let Ok(mut stream) = std::net::TcpStream::connect("127.0.0.1:11210") {
-        // An instance is already bound to our address.
-        // We send a dummy packet and then exit.
-        std::io::Write::write_all(&mut stream, &[1]).unwrap();
-        return;
+    // Instead of using `single_instance` for this, Kumo listens on a per-user socket,
+    // or a TCP port if it can't. See `ipc::Endpoint`.
+    // So we first check if one of these is already occupied:
+    let mut endpoints = ipc::Endpoint::candidates(port);
+    // A running instance only listens to us if we know its secret.
+    // Without one, we can't talk to it, but the probe still finds occupied endpoints.
+    let secret = ipc::Secret::load().unwrap_or_else(|_| ipc::Secret::generate());
+    let mut index = 0;
+    while index < endpoints.len() {
+        let Ok(mut stream) = endpoints[index].connect() else {
+            index += 1;
+            continue;
+        };
+        // Something is already listening.
+        // We send it our request, and if it's Kumo, it responds.
+        stream.set_timeout(Some(IPC_TIMEOUT)).unwrap();
+        match ipc::send(&mut stream, &secret, &request) {
+            Ok(response) => report(response),
+            // It's Kumo, but it didn't answer, maybe because it's busy or has another secret.
+            // Starting anyway would run a second instance.
+            Err(e) if endpoints[index].is_kumo_only() => {
+                eprintln!("Kumo is running on {}, but didn't answer: {e}", endpoints[index]);
+                std::process::exit(1);
+            }
+            // It's some other program, so we can't listen there.
+            Err(_) => {
+                endpoints.remove(index);
+            }
+        }
+    }
+    // Only requests for the interface are worth starting an instance for.
+    match request {
+        ipc::Request::Hide | ipc::Request::Quit | ipc::Request::Status => {
+            eprintln!("Kumo is not running.");
+            std::process::exit(1);
+        }
+        _ => {}
+    }
+    // No other instance is running - we listen on the first endpoint we can,
+    // and start a new session with a new secret.
+    let listener = endpoints.iter().find_map(|endpoint| match endpoint.bind() {
+        Ok(listener) => {
+            println!("Now listening on {endpoint}");
+            Some(listener)
+        }
+        Err(e) => {
+            eprintln!("Cannot listen on {endpoint}: {e}");
+            None
+        }
+    }).unwrap_or_else(|| cannot_listen(port));
+    // The secret is only replaced once the endpoint is ours. Otherwise a running instance
+    // we failed to reach would stop accepting requests, since they'd carry our secret.
+    let secret = ipc::Secret::generate();
+    if let Err(e) = secret.save() {
+        eprintln!("Cannot write {}, other processes won't reach Kumo: {e}", ipc::Secret::path().display());
     }
-    // No other instance is running - we bind to our address.
-    let listener = std::net::TcpListener::bind("127.0.0.1:11210").unwrap();
-    println!("Now listening on 127.0.0.1:11210");
     // We can save a thread and channel by using the listener in a nonblocking way.
     listener.set_nonblocking(true).unwrap();
 
@@ -32,54 +91,149 @@ fn main() {
 
     // Then, we build our Bevy app.
     // This is somewhat expensive, which is why we don't do it at the complete start.
-    let mut app = kumo::build();
+    let mut app = kumo::build(&cli);
 
     // Our listener is added to the app as resource, so we can use it in systems.
-    app.insert_resource(InstanceListener(listener));
+    app.insert_resource(InstanceListener { listener, secret });
     // And we add the system that reads the listener:
     app.add_system(instance_system);
 
+    // The window is shown at start anyway, which is also what toggling it does.
+    // Other requests are handled like those of other processes.
+    if !matches!(request, ipc::Request::Show | ipc::Request::Toggle) {
+        app.world.send_event(request);
+    }
+
     // Finally, we start our app.
     app.run();
 }
 
+/// Reports the response of the running instance, and exits.
+fn report(response: ipc::Response) -> ! {
+    match response {
+        ipc::Response::Ok => std::process::exit(0),
+        ipc::Response::Status(status) => {
+            println!("Kumo {} is running.", status.version);
+            println!("Window: {}", if status.window_visible { "visible" } else { "hidden" });
+            println!("DeviantArt: {}", status.deviantart);
+            println!("Meilisearch: {}", status.meilisearch);
+            std::process::exit(0);
+        }
+        ipc::Response::Error { message } => {
+            eprintln!("The running instance of Kumo refused: {message}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Exits with an explanation, as we can't run without other instances finding us.
+fn cannot_listen(port: u16) -> ! {
+    eprintln!(
+        "Kumo cannot listen for other instances, so it cannot start.\n\
+        If port {port} is used by another program, \
+        choose another port with --instance-port or in {}.",
+        settings::Settings::path().display()
+    );
+    std::process::exit(1);
+}
+
 #[derive(Resource)]
-struct InstanceListener(std::net::TcpListener);
+struct InstanceListener {
+    listener: ipc::Listener,
+    secret: ipc::Secret,
+}
 
+#[allow(clippy::too_many_arguments)]
 fn instance_system(
-    mut commands: Commands,
-    mut window: ResMut<window::WindowEntity>,
     listener: Res<InstanceListener>,
-    windows: NonSend<bevy::winit::WinitWindows>
+    // These don't exist when running headless.
+    window: Option<Res<window::WindowEntity>>,
+    mut window_commands: Option<ResMut<Events<window::WindowCommand>>>,
+    auth_state: Res<State<deviantart::AuthState>>,
+    settings: Res<settings::Settings>,
+    meili_status: Res<meiliguard::MeilisearchStatus>,
+    mut forward: EventWriter<ipc::Request>,
+    mut exit: EventWriter<bevy::app::AppExit>,
 ) {
-    // Check if we can set the icon of our window.
-    if window.needs_icon {
-        window.needs_icon = false;
-        let window_id = windows.entity_to_winit.get(&window.id).unwrap();
-        let winit_window = windows.windows.get(window_id).unwrap();
-        winit_window.set_window_icon(Some(window::icon()));
+    // It is perfectly fine to only accept a single connection per Bevy update.
+    let Ok((mut stream, addr)) = listener.listener.accept() else {
+        return;
+    };
+
+    // The connection is served in one go. The timeout keeps a client that
+    // doesn't follow the protocol from stalling us for long.
+    if let Err(e) = stream.set_timeout(Some(IPC_READ_TIMEOUT)) {
+        warn!("Dropping IPC connection from {addr}: {e}");
+        return;
     }
 
-    // It is perfectly fine to only accept a single connection per Bevy update.
-    if let Ok((stream, _addr)) = listener.0.accept() {
-        // We restrict the buffer window so we cannot overrun our tiny buffer.
-        let mut handle = std::io::Read::take(stream , 1);
-        let mut buf = [0u8; 1];
-        std::io::Read::read(&mut handle, &mut buf).unwrap();
-        // The buffer starts off as [0], however, if we received message from another
-        // Kumo instance, it will be set to [1]. We can then act accordingly:
-        if buf[0] == 1u8 {
-            // In this case, we want to create a new window,
-            // if we don't already have a window.
-            if commands.get_entity(window.id).is_none() {
-                // A primary window doesn't exist, so we create one.
-                // Since we must work with commands instead of with the app,
-                // this code is essentially duplicate of that in `window`.
-                // TODO: Clean single-instancing window creation up.
-                window.id = commands.spawn(window::window())
-                    .insert(bevy::window::PrimaryWindow).id();
-                window.needs_icon = true;
+    // Connections that don't follow the protocol, or don't know the secret, are dropped.
+    let request = match ipc::read_request(&mut stream, &listener.secret) {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Dropping IPC connection from {addr}: {e}");
+            return;
+        }
+    };
+
+    let response = match (request, &mut window_commands) {
+        (Ok(ipc::Request::Show | ipc::Request::Hide | ipc::Request::Toggle), None) => ipc::Response::Error {
+            message: "Kumo is running headless, without a window".to_owned(),
+        },
+        (Ok(ipc::Request::Show), Some(window_commands)) => {
+            window_commands.send(window::WindowCommand::Show);
+            ipc::Response::Ok
+        }
+        (Ok(ipc::Request::Hide), Some(window_commands)) => {
+            window_commands.send(window::WindowCommand::Hide);
+            ipc::Response::Ok
+        }
+        (Ok(ipc::Request::Toggle), Some(window_commands)) => {
+            window_commands.send(window::WindowCommand::Toggle);
+            ipc::Response::Ok
+        }
+        (Ok(ipc::Request::Quit), _) => {
+            exit.send(bevy::app::AppExit);
+            ipc::Response::Ok
+        }
+        (Ok(ipc::Request::Status), _) => ipc::Response::Status(ipc::Status {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            window_visible: window.is_some_and(|window| window.visible),
+            deviantart: format!("{:?}", auth_state.0),
+            meilisearch: meili_status.to_string(),
+        }),
+        // The interface and DeviantArt take care of these.
+        (Ok(ipc::Request::Open { url }), window_commands) => match deviantart::Link::parse(&url) {
+            Ok(_) => {
+                // The user wants to see what they opened.
+                if let Some(window_commands) = window_commands {
+                    window_commands.send(window::WindowCommand::Show);
+                }
+                forward.send(ipc::Request::Open { url });
+                ipc::Response::Ok
+            }
+            Err(e) => ipc::Response::Error { message: format!("Cannot open {url}: {e}") },
+        },
+        (Ok(ipc::Request::Authenticate), _) => {
+            match deviantart::can_authenticate(auth_state.0, &settings.deviantart) {
+                Ok(()) => {
+                    forward.send(ipc::Request::Authenticate);
+                    ipc::Response::Ok
+                }
+                Err(message) => ipc::Response::Error { message },
             }
         }
+        (Ok(request @ ipc::Request::Search { .. }), _) => {
+            forward.send(request);
+            ipc::Response::Ok
+        }
+        (Err(message), _) => {
+            warn!("Refusing IPC request from {addr}: {message}");
+            ipc::Response::Error { message }
+        }
+    };
+
+    if let Err(e) = ipc::write_response(&mut stream, &response) {
+        warn!("Failed to respond to IPC request from {addr}: {e}");
     }
-}
\ No newline at end of file
+}
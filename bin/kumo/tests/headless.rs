@@ -0,0 +1,132 @@
+//! Builds Kumo headless, as on a machine without a GPU,
+//! with its config in a temporary directory and DeviantArt replaced by mockart.
+
+use bevy::prelude::*;
+use clap::Parser;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// A token that expired long ago, so Kumo refreshes it right away.
+const EXPIRED_TOKEN: &str = r#"{"response":{"access_token":"expired-access-token","token_type":"bearer","expires_in":3600,"refresh_token":"mock-refresh-token","scope":"browse"},"expires_at":0}"#;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("kumo-test-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Updates the app until the condition holds, or fails after a while.
+fn update_until(app: &mut App, what: &str, condition: impl Fn(&World) -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !condition(&app.world) {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        app.update();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn auth_state(world: &World) -> deviantart::AuthState {
+    world.resource::<State<deviantart::AuthState>>().0
+}
+
+#[test]
+fn headless() {
+    // Settings and the token are read from here, instead of the user's config.
+    let config_dir = temp_dir("config");
+    std::env::set_var("XDG_CONFIG_HOME", &config_dir);
+    let kumo_dir = config_dir.join("kumo");
+    std::fs::create_dir_all(&kumo_dir).unwrap();
+    std::fs::write(
+        kumo_dir.join("settings.toml"),
+        "[deviantart]\nclient_id = \"12345\"\nclient_secret = \"0123456789abcdef\"\n",
+    )
+    .unwrap();
+    std::fs::write(kumo_dir.join("deviantart.token"), EXPIRED_TOKEN).unwrap();
+
+    // There's no Meilisearch in here.
+    let meili_dir = temp_dir("meili");
+
+    let server = mockart::MockServer::start().unwrap();
+    let cli = kumo::cli::Cli::parse_from([
+        "kumo".to_owned(),
+        "--headless".to_owned(),
+        format!("--meili={}", meili_dir.display()),
+        "--oauth-port=11999".to_owned(),
+        format!("--deviantart-auth-url={}", server.auth_url()),
+        format!("--deviantart-token-url={}", server.token_url()),
+        format!("--deviantart-api-url={}", server.api_url()),
+    ]);
+    let mut app = kumo::build(&cli);
+    app.update();
+
+    // Nothing that needs a window or GPU was added.
+    assert!(!app.world.contains_resource::<window::WindowEntity>());
+
+    let settings = app.world.resource::<settings::Settings>();
+    assert_eq!(settings.deviantart.client_id, "12345");
+    assert!(!settings.load_failed);
+    assert_eq!(app.world.resource::<settings::Ports>().oauth, 11999);
+    assert_eq!(app.world.resource::<deviantart::Endpoints>().token_url, server.token_url());
+
+    assert!(matches!(
+        app.world.resource::<meiliguard::MeilisearchStatus>(),
+        meiliguard::MeilisearchStatus::Unavailable(_)
+    ));
+
+    // The expired token from disk is refreshed with mockart.
+    let token_path = kumo_dir.join("deviantart.token");
+    update_until(&mut app, "the token to be refreshed", |world| {
+        auth_state(world) == deviantart::AuthState::Authenticated
+            && std::fs::read_to_string(&token_path).unwrap().contains("mock-access-token")
+    });
+    let refresh = server.requests().into_iter().find(|request| request.path == "/oauth2/token").unwrap();
+    assert!(refresh.body.contains("grant_type=refresh_token"));
+    assert!(refresh.body.contains("refresh_token=mock-refresh-token"));
+
+    let token = app.world.resource::<deviantart::TokenResponse>();
+    assert_eq!(token.scopes(), ["browse"]);
+
+    // API calls use the refreshed token.
+    server.set_api_response(
+        "/user/whoami",
+        200,
+        r#"{"userid":"1","username":"someone","usericon":"","type":"regular"}"#,
+    );
+    let client = token.api_client(app.world.resource::<deviantart::Endpoints>());
+    assert_eq!(client.whoami().unwrap().username, "someone");
+    let whoami = server.requests().into_iter().find(|request| request.path.ends_with("/user/whoami")).unwrap();
+    assert_eq!(whoami.header("authorization"), Some("Bearer mock-access-token"));
+
+    // Changing the OAuth2 port in the settings moves the redirect right away,
+    // even though the port in use was given on the command line.
+    app.world.resource_mut::<settings::Settings>().ports.oauth = 12001;
+    app.update();
+    assert_eq!(app.world.resource::<deviantart::Endpoints>().redirect_url, "http://localhost:12001");
+    assert_eq!(app.world.resource::<settings::Ports>().oauth, 12001);
+    assert_eq!(auth_state(&app.world), deviantart::AuthState::Authenticated);
+
+    app.world.send_event(deviantart::Logout);
+    update_until(&mut app, "logging out", |world| {
+        auth_state(world) == deviantart::AuthState::Unauthenticated
+    });
+    assert!(!app.world.contains_resource::<deviantart::TokenResponse>());
+    assert!(!token_path.exists());
+
+    // Requests from other processes reach the plugins. Without credentials,
+    // authenticating fails before it would open a browser.
+    let mut settings = app.world.resource_mut::<settings::Settings>();
+    settings.deviantart.client_id.clear();
+    settings.deviantart.client_secret.clear();
+    app.update();
+    app.world.send_event(ipc::Request::Authenticate);
+    update_until(&mut app, "authentication to fail", |world| {
+        auth_state(world) == deviantart::AuthState::Failed
+    });
+    let failures = app.world.resource::<Events<deviantart::AuthenticationFailed>>();
+    let failure = failures.get_reader().iter(failures).last().map(|failure| failure.0.clone());
+    assert_eq!(failure.as_deref(), Some("DeviantArt credentials are not configured"));
+
+    std::fs::remove_dir_all(&config_dir).unwrap();
+    std::fs::remove_dir_all(&meili_dir).unwrap();
+}
@@ -3,37 +3,416 @@
 //! On Unix, Meili is executed by a thin wrapper process (main.rs).
 
 use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use meilisearch_sdk::errors::{Error, ErrorCode};
+use meilisearch_sdk::key::{Action, KeyBuilder};
 use meilisearch_sdk::Client;
-
+use std::sync::mpsc::Receiver;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long to wait before probing a Meilisearch that isn't healthy yet.
+/// This doubles after every failed probe, up to [`HEALTH_INTERVAL`].
+const FIRST_RETRY: Duration = Duration::from_millis(100);
+/// How often a healthy Meilisearch is probed.
+const HEALTH_INTERVAL: Duration = Duration::from_secs(5);
+/// How long Meilisearch may take to become healthy before it's considered unavailable.
+/// Opening a large database takes a while.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The API keys Kumo uses have fixed UIDs, so they're created once per database,
+/// and found again on later runs.
+const SEARCH_KEY_UID: &str = "3f0b1c7e-9a4d-4e52-8c1f-6d2a5b7e9c01";
+const ADMIN_KEY_UID: &str = "8d6e2a94-1b3c-4f70-a5e8-0c9d7f4b2e13";
+
+/// Clients are only authorized once Meilisearch is ready, see [`MeilisearchStatus`].
 #[derive(Resource)]
-pub struct Meilisearch(pub Client);
+pub struct Meilisearch {
+    /// Uses the admin key, which can do everything but manage API keys.
+    pub client: Client,
+    /// Uses the search key, which can only search.
+    pub search_client: Client,
+    /// How often the guard restarted Meilisearch after it exited.
+    pub restarts: u32,
+    /// How Meilisearch last exited, e.g. `exit status: 1`, if it did.
+    pub last_exit: Option<String>,
+    /// The guard on Unix, Meilisearch itself on Windows.
+    /// None if it couldn't be started, or has exited.
+    process: Option<Child>,
+    /// What the guard reports, read from its stdout. Only on Unix.
+    reports: Option<Mutex<Receiver<GuardReport>>>,
+    /// Uses the master key, which is only needed to create the other keys.
+    master_client: Client,
+    has_keys: bool,
+}
+
+/// The keys the clients use. Meilisearch derives them from the master key.
+struct ApiKeys {
+    search: String,
+    admin: String,
+}
+
+/// What the guard reports to Kumo, one per line on its stdout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GuardReport {
+    /// Meilisearch exited, as described by its exit status.
+    Exited(String),
+    /// Meilisearch was started again, for the given time.
+    Restarted(u32),
+    /// Meilisearch exited too often in a row, or couldn't be started again,
+    /// so the guard gave up and exits.
+    GaveUp,
+}
+
+impl std::fmt::Display for GuardReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exited(status) => write!(f, "exited {status}"),
+            Self::Restarted(restarts) => write!(f, "restarted {restarts}"),
+            Self::GaveUp => write!(f, "gave-up"),
+        }
+    }
+}
 
-impl Default for Meilisearch {
-    fn default() -> Self {
-        Self(start())
+impl std::str::FromStr for GuardReport {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        match line.split_once(' ').unwrap_or((line, "")) {
+            ("exited", status) if !status.is_empty() => Ok(Self::Exited(status.to_owned())),
+            ("restarted", restarts) => restarts.parse().map(Self::Restarted).map_err(|e| e.to_string()),
+            ("gave-up", "") => Ok(Self::GaveUp),
+            _ => Err(format!("Unknown report `{line}`")),
+        }
     }
 }
 
-pub struct MeilisearchPlugin;
+/// Whether Meilisearch answers, as found by probing its `/health` endpoint.
+#[derive(Resource, Clone, Debug, PartialEq, Eq)]
+pub enum MeilisearchStatus {
+    /// Meilisearch was started, but hasn't answered yet.
+    Starting,
+    Ready,
+    /// Meilisearch was ready, but stopped answering.
+    Crashed(String),
+    /// Meilisearch never became ready, e.g. because it's missing,
+    /// or because its database is corrupted.
+    Unavailable(String),
+}
+
+impl std::fmt::Display for MeilisearchStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Starting => write!(f, "Starting"),
+            Self::Ready => write!(f, "Ready"),
+            Self::Crashed(e) => write!(f, "Crashed ({e})"),
+            Self::Unavailable(e) => write!(f, "Unavailable ({e})"),
+        }
+    }
+}
+
+/// The state of probing Meilisearch's health.
+#[derive(Resource)]
+struct HealthProbe {
+    /// Also gets the API keys, if they're yet unknown.
+    task: Option<Task<Result<Option<ApiKeys>, String>>>,
+    next: Instant,
+    retry: Duration,
+    started: Instant,
+    /// Where Meilisearch tells why it failed.
+    log_path: PathBuf,
+}
+
+#[derive(Default)]
+pub struct MeilisearchPlugin {
+    /// The meilisearch operating directory, see [`meili_dir`].
+    /// Defaults to the executable's directory.
+    pub meili_dir: Option<PathBuf>,
+}
 
 impl Plugin for MeilisearchPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<Meilisearch>();
+        let port = app.world
+            .get_resource::<settings::Ports>()
+            .copied()
+            .unwrap_or_default()
+            .meilisearch;
+
+        // Meilisearch would only tell us about this in its log file.
+        if let Err(e) = std::net::TcpListener::bind(("127.0.0.1", port)) {
+            error!(
+                "Port {port} for Meilisearch is unavailable ({e}). \
+                It may be used by another program, or by a Meilisearch left over from a crash. \
+                Choose another port with --meili-port or in {}.",
+                settings::Settings::path().display()
+            );
+        }
+
+        let meili_dir = self.meili_dir.clone().unwrap_or_else(|| {
+            let exe_path = std::env::current_exe().unwrap();
+            exe_path.parent().unwrap().to_owned()
+        });
+
+        let grace_period = app.world
+            .get_resource::<settings::Settings>()
+            .map_or(DEFAULT_GRACE_PERIOD, |settings| {
+                Duration::from_secs(settings.meilisearch.shutdown_grace_period)
+            });
+
+        let master_key = master_key(&meili_dir);
+        let (mut process, status) = match master_key.as_ref().map(|key| start(port, &meili_dir, key, grace_period)) {
+            Ok(Ok(process)) => (Some(process), MeilisearchStatus::Starting),
+            Err(e) => {
+                let e = format!("cannot get its master key: {e}");
+                error!("Meilisearch {e}");
+                (None, MeilisearchStatus::Unavailable(e))
+            }
+            Ok(Err(e)) => {
+                let e = format!("cannot start it in {}: {e}", meili_dir.display());
+                error!("Meilisearch {e}");
+                (None, MeilisearchStatus::Unavailable(e))
+            }
+        };
+        let reports = process.as_mut()
+            .and_then(|process| process.stdout.take())
+            .map(|stdout| Mutex::new(read_reports(stdout)));
+
+        app.insert_resource(status);
+        let host = format!("http://localhost:{port}");
+        app.insert_resource(Meilisearch {
+            client: Client::new(&host, None::<String>),
+            search_client: Client::new(&host, None::<String>),
+            restarts: 0,
+            last_exit: None,
+            process,
+            reports,
+            master_client: Client::new(&host, master_key.ok()),
+            has_keys: false,
+        });
+        app.insert_resource(HealthProbe {
+            task: None,
+            next: Instant::now(),
+            retry: FIRST_RETRY,
+            started: Instant::now(),
+            log_path: meili_dir.join("meilisearch.log"),
+        });
+        app.add_system(report_system.before(health_system));
+        app.add_system(health_system);
     }
 }
 
-const ADDR: &str = "localhost:11212";
+/// Reads the guard's reports on a thread, as reading blocks.
+fn read_reports(stdout: ChildStdout) -> Receiver<GuardReport> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            match line.parse() {
+                // The receiver goes with the `Meilisearch` resource, when Kumo exits.
+                Ok(report) => if sender.send(report).is_err() {
+                    return;
+                },
+                Err(e) => warn!("Meilisearch guard: {e}"),
+            }
+        }
+    });
+    receiver
+}
+
+/// Applies what the guard reports about restarting Meilisearch.
+fn report_system(
+    mut meili: ResMut<Meilisearch>,
+    mut status: ResMut<MeilisearchStatus>,
+    mut probe: ResMut<HealthProbe>,
+) {
+    let Some(reports) = meili.reports.as_mut() else {
+        return;
+    };
+    let reports: Vec<_> = reports.get_mut().unwrap().try_iter().collect();
+
+    for report in reports {
+        match report {
+            GuardReport::Exited(exit_status) => {
+                let e = format!("exited with {exit_status}, see {}", probe.log_path.display());
+                error!("Meilisearch {e}");
+                if *status == MeilisearchStatus::Ready {
+                    *status = MeilisearchStatus::Crashed(e);
+                }
+                meili.last_exit = Some(exit_status);
+            }
+            GuardReport::Restarted(restarts) => {
+                warn!("Meilisearch was restarted, {restarts} time(s) so far");
+                meili.restarts = restarts;
+                // It has to become ready again.
+                *status = MeilisearchStatus::Starting;
+                probe.started = Instant::now();
+                probe.retry = FIRST_RETRY;
+                probe.next = Instant::now();
+            }
+            GuardReport::GaveUp => {
+                // It exited too often, or couldn't be started again.
+                let e = format!("couldn't be kept running, see {}", probe.log_path.display());
+                error!("Meilisearch {e}");
+                *status = MeilisearchStatus::Unavailable(e);
+            }
+        }
+    }
+}
+
+/// Probes Meilisearch's health, with backoff until it's healthy,
+/// and notices when the process exits.
+fn health_system(
+    mut meili: ResMut<Meilisearch>,
+    mut status: ResMut<MeilisearchStatus>,
+    mut probe: ResMut<HealthProbe>,
+) {
+    // The process exiting is the first sign of Meilisearch failing.
+    if let Some(Ok(Some(exit_status))) = meili.process.as_mut().map(Child::try_wait) {
+        meili.process = None;
+        // The guard already told why it gave up.
+        if !matches!(*status, MeilisearchStatus::Unavailable(_)) {
+            let e = format!("exited with {exit_status}, see {}", probe.log_path.display());
+            error!("Meilisearch {e}");
+            *status = match *status {
+                MeilisearchStatus::Ready => MeilisearchStatus::Crashed(e),
+                _ => MeilisearchStatus::Unavailable(e),
+            };
+        }
+    }
+
+    if let Some(task) = &mut probe.task {
+        let Some(result) = futures_lite::future::block_on(futures_lite::future::poll_once(task)) else {
+            return;
+        };
+        probe.task = None;
+
+        match result {
+            Ok(keys) => {
+                if let Some(keys) = keys {
+                    let host = meili.client.get_host().to_owned();
+                    meili.client = Client::new(&host, Some(keys.admin));
+                    meili.search_client = Client::new(&host, Some(keys.search));
+                    meili.has_keys = true;
+                }
+                if *status != MeilisearchStatus::Ready {
+                    info!("Meilisearch is ready");
+                    *status = MeilisearchStatus::Ready;
+                }
+                probe.retry = HEALTH_INTERVAL;
+            }
+            Err(e) => {
+                let e = format!("{e}, see {}", probe.log_path.display());
+                // Backing off starts over once Meilisearch fails after being ready.
+                probe.retry = match *status {
+                    MeilisearchStatus::Ready => FIRST_RETRY,
+                    _ => (probe.retry * 2).min(HEALTH_INTERVAL),
+                };
+                match *status {
+                    MeilisearchStatus::Starting if probe.started.elapsed() < STARTUP_TIMEOUT => (),
+                    MeilisearchStatus::Starting => {
+                        error!("Meilisearch didn't become ready: {e}");
+                        *status = MeilisearchStatus::Unavailable(e);
+                    }
+                    MeilisearchStatus::Ready => {
+                        error!("Meilisearch stopped answering: {e}");
+                        *status = MeilisearchStatus::Crashed(e);
+                    }
+                    // We already told.
+                    MeilisearchStatus::Crashed(_) | MeilisearchStatus::Unavailable(_) => (),
+                }
+            }
+        }
+        probe.next = Instant::now() + probe.retry;
+    }
 
-const HOST: &str = "http://localhost:11212";
+    if probe.task.is_none() && Instant::now() >= probe.next {
+        let master_client = meili.master_client.clone();
+        let has_keys = meili.has_keys;
+        probe.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+            match master_client.health().await {
+                Ok(health) if health.status == "available" => (),
+                Ok(health) => return Err(format!("Meilisearch is {}", health.status)),
+                Err(e) => return Err(e.to_string()),
+            }
+            if has_keys {
+                return Ok(None);
+            }
+            api_keys(&master_client).await
+                .map(Some)
+                .map_err(|e| format!("cannot create API keys: {e}"))
+        }));
+    }
+}
+
+/// Gets the API keys, creating them if this database doesn't have them yet.
+async fn api_keys(master_client: &Client) -> Result<ApiKeys, Error> {
+    Ok(ApiKeys {
+        search: api_key(master_client, SEARCH_KEY_UID, "Kumo search", &[Action::Search]).await?,
+        admin: api_key(master_client, ADMIN_KEY_UID, "Kumo admin", &[
+            Action::Search,
+            Action::DocumentsAdd,
+            Action::DocumentsGet,
+            Action::DocumentsDelete,
+            Action::IndexesCreate,
+            Action::IndexesGet,
+            Action::IndexesUpdate,
+            Action::IndexesDelete,
+            Action::TasksGet,
+            Action::SettingsGet,
+            Action::SettingsUpdate,
+            Action::StatsGet,
+            Action::DumpsCreate,
+            Action::Version,
+        ]).await?,
+    })
+}
+
+async fn api_key(master_client: &Client, uid: &str, name: &str, actions: &[Action]) -> Result<String, Error> {
+    match master_client.get_key(uid).await {
+        Ok(key) => return Ok(key.key),
+        Err(Error::Meilisearch(e)) if e.error_code == ErrorCode::ApiKeyNotFound => (),
+        Err(e) => return Err(e),
+    }
+
+    let mut builder = KeyBuilder::new();
+    builder.with_name(name).with_actions(actions.iter().copied()).with_index("*");
+    builder.uid = Some(uid.to_owned());
+    Ok(master_client.create_key(builder).await?.key)
+}
 
+/// Returns the master key of the database in the directory, generating it on first run.
+/// It's kept in a file only the user can read, and given to Meilisearch in its environment,
+/// since other users can read command lines.
+///
+/// It's generated like the IPC secret, by [`settings::random_hex_key`];
+/// `eprng` isn't meant for keys.
+fn master_key(meili_dir: &Path) -> std::io::Result<String> {
+    let path = meili_dir.join("master.key");
+    match std::fs::read_to_string(&path) {
+        Ok(key) if !key.trim().is_empty() => return Ok(key.trim().to_owned()),
+        // An empty file, e.g. from a write that failed halfway, is as good as none.
+        Ok(_) => (),
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        Err(_) => (),
+    }
+
+    let key = settings::random_hex_key();
+    settings::write_private(&path, key.as_bytes())?;
+    Ok(key)
+}
+
+use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Child, ChildStdout, Command, Stdio};
 
 /// Returns the meilisearch operating directory.<br>
 /// This directory contains the meilisearch executable,<br>
 /// the meilisearch guard executable, and the database.<br>
 /// This is path/to/exe by default, or whichever path was<br>
-/// provided through `--meili={path}`.
+/// provided through `--meili={path}`.<br>
+/// Kumo passes its operating directory to the guard this way.
 pub fn meili_dir(exe_dir: impl Into<PathBuf>) -> PathBuf {
     std::env::args()
         .find(|s| s.starts_with("--meili="))
@@ -41,6 +420,18 @@ pub fn meili_dir(exe_dir: impl Into<PathBuf>) -> PathBuf {
         .unwrap_or(exe_dir.into())
 }
 
+/// How long meilisearch may take to exit before the guard kills it,
+/// unless Kumo passes another through `--grace-period={seconds}`.
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Returns the grace period Kumo passed to the guard, see [`DEFAULT_GRACE_PERIOD`].
+pub fn grace_period() -> Duration {
+    std::env::args()
+        .take_while(|s| s != "--")
+        .find_map(|s| s.strip_prefix("--grace-period=").and_then(|secs| secs.parse().ok()))
+        .map_or(DEFAULT_GRACE_PERIOD, Duration::from_secs)
+}
+
 /// Returns the path to the meilisearch executable.
 pub fn meili_path(meili_dir: impl AsRef<Path>) -> PathBuf {
     meili_dir
@@ -61,59 +452,55 @@ fn guard_path(meili_dir: impl AsRef<Path>) -> PathBuf {
 /// This wrapper is inherited by init if Kumo exits and terminates<br>
 /// meilisearch and then itself by listening to PDEATHSIG.
 #[cfg(unix)]
-fn start() -> Client {
-    let exe_path = std::env::current_exe().unwrap();
-    let exe_dir = exe_path.parent().unwrap();
-
-    let meili_dir = meili_dir(exe_dir);
-    let guard_path = guard_path(&meili_dir);
+fn start(port: u16, meili_dir: &Path, master_key: &str, grace_period: Duration) -> std::io::Result<Child> {
+    let guard_path = guard_path(meili_dir);
 
     let out_path = meili_dir.join("meilisearch.log");
-    let out = std::fs::File::create(out_path).unwrap();
+    let out = std::fs::File::create(out_path)?;
 
     let db_path = meili_dir.join("data.ms");
     let dump_dir = meili_dir.join("dumps/");
 
     let mut command = Command::new(guard_path);
     command.arg(format!("--meili={}", meili_dir.display()));
+    command.arg(format!("--grace-period={}", grace_period.as_secs()));
     command.arg("--");
     command.arg("--no-analytics");
-    command.arg(format!("--http-addr={}", ADDR));
+    command.arg(format!("--http-addr=localhost:{port}"));
     command.arg(format!("--db-path={}", db_path.display()));
     command.arg(format!("--dump-dir={}", dump_dir.display()));
-    command.stdout(Stdio::from(out.try_clone().unwrap()));
+    command.env("MEILI_MASTER_KEY", master_key);
+    // The guard reports on stdout, see [`GuardReport`].
+    // Meilisearch writes its own output to the guard's stderr.
+    command.stdout(Stdio::piped());
     command.stderr(Stdio::from(out));
-    command.spawn().unwrap();
-
-    Client::new(HOST, None::<String>)
+    command.spawn()
 }
 
 /// Starts meilisearch with the Windows-guard.<br>
 /// Works by designating meilisearch as job object and<br>
-/// setting the `JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE` flag.
+/// setting the `JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE` flag.<br>
+/// Windows has no way to ask it to exit, so there's no grace period.
 #[cfg(windows)]
-fn start() -> Client {
-    let exe_path = std::env::current_exe().unwrap();
-    let exe_dir = exe_path.parent().unwrap();
-
-    let meili_dir = meili_dir(exe_dir);
-    let meili_path = meili_path(&meili_dir);
+fn start(port: u16, meili_dir: &Path, master_key: &str, _grace_period: Duration) -> std::io::Result<Child> {
+    let meili_path = meili_path(meili_dir);
 
     let out_path = meili_dir.join("meilisearch.log");
-    let out = std::fs::File::create(out_path).unwrap();
+    let out = std::fs::File::create(out_path)?;
 
     let db_path = meili_dir.join("data.ms");
     let dump_dir = meili_dir.join("dumps/");
 
     let mut command = Command::new(meili_path);
     command.arg("--no-analytics");
-    command.arg(format!("--http-addr={}", ADDR));
+    command.arg(format!("--http-addr=localhost:{port}"));
     command.arg(format!("--db-path={}", db_path.display()));
     command.arg(format!("--dump-dir={}", dump_dir.display()));
-    command.stdout(Stdio::from(out.try_clone().unwrap()));
+    command.env("MEILI_MASTER_KEY", master_key);
+    command.stdout(Stdio::from(out.try_clone()?));
     command.stderr(Stdio::from(out));
 
-    let meili = command.spawn().unwrap();
+    let meili = command.spawn()?;
 
     unsafe {
         use std::os::windows::prelude::AsRawHandle;
@@ -141,5 +528,185 @@ fn start() -> Client {
         );
     }
 
-    Client::new(HOST, None::<String>)
+    Ok(meili)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    /// A stand-in for Meilisearch's `/health` endpoint, answering with the given status,
+    /// or not at all if there is none.
+    struct Health {
+        url: String,
+        status: Arc<Mutex<Option<&'static str>>>,
+    }
+
+    impl Health {
+        fn start(status: Option<&'static str>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let status = Arc::new(Mutex::new(status));
+
+            let answer = status.clone();
+            std::thread::spawn(move || {
+                for mut stream in listener.incoming().flatten() {
+                    // The request doesn't matter, but has to be read before answering.
+                    let mut reader = BufReader::new(&stream);
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                        line.clear();
+                    }
+
+                    let Some(status) = *answer.lock().unwrap() else {
+                        continue;
+                    };
+                    let body = format!(r#"{{"status":"{status}"}}"#);
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                        content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                }
+            });
+
+            Self { url, status }
+        }
+
+        fn set(&self, status: Option<&'static str>) {
+            *self.status.lock().unwrap() = status;
+        }
+    }
+
+    fn app(health: &Health) -> App {
+        let mut app = App::new();
+        app.add_plugin(TaskPoolPlugin::default());
+        app.insert_resource(MeilisearchStatus::Starting);
+        app.insert_resource(Meilisearch {
+            client: Client::new(&health.url, None::<String>),
+            search_client: Client::new(&health.url, None::<String>),
+            restarts: 0,
+            last_exit: None,
+            process: None,
+            reports: None,
+            master_client: Client::new(&health.url, Some("master-key")),
+            // So only the health is probed.
+            has_keys: true,
+        });
+        app.insert_resource(HealthProbe {
+            task: None,
+            next: Instant::now(),
+            retry: FIRST_RETRY,
+            started: Instant::now(),
+            log_path: PathBuf::from("meilisearch.log"),
+        });
+        app.add_system(health_system);
+        app
+    }
+
+    /// Updates the app until the status is no longer the given one, and returns the new one.
+    fn next_status(app: &mut App, current: &MeilisearchStatus) -> MeilisearchStatus {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            app.update();
+            let status = app.world.resource::<MeilisearchStatus>();
+            if status != current {
+                return status.clone();
+            }
+            assert!(Instant::now() < deadline, "still {current}");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn reports() {
+        for report in [
+            GuardReport::Exited("exit status: 1".to_owned()),
+            GuardReport::Exited("signal: 9 (SIGKILL)".to_owned()),
+            GuardReport::Restarted(3),
+            GuardReport::GaveUp,
+        ] {
+            assert_eq!(report.to_string().parse(), Ok(report));
+        }
+        assert_eq!("restarted 2".parse(), Ok(GuardReport::Restarted(2)));
+    }
+
+    #[test]
+    fn malformed_reports() {
+        for line in ["", "exited", "exited ", "restarted", "restarted x", "restarted -1", "gave-up extra", "crashed 1"] {
+            assert!(line.parse::<GuardReport>().is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn master_keys() {
+        let meili_dir = std::env::temp_dir().join(format!("meiliguard-test-{}", std::process::id()));
+        let key = master_key(&meili_dir).unwrap();
+        assert_eq!(key.len(), 64);
+        assert_eq!(master_key(&meili_dir).unwrap(), key);
+
+        std::fs::write(meili_dir.join("master.key"), " \n").unwrap();
+        let new_key = master_key(&meili_dir).unwrap();
+        assert_eq!(new_key.len(), 64);
+        assert_ne!(new_key, key);
+        assert_eq!(std::fs::read_to_string(meili_dir.join("master.key")).unwrap(), new_key);
+
+        std::fs::remove_dir_all(&meili_dir).unwrap();
+    }
+
+    #[test]
+    fn becomes_ready() {
+        let health = Health::start(Some("available"));
+        let mut app = app(&health);
+        assert_eq!(next_status(&mut app, &MeilisearchStatus::Starting), MeilisearchStatus::Ready);
+        assert_eq!(app.world.resource::<HealthProbe>().retry, HEALTH_INTERVAL);
+    }
+
+    #[test]
+    fn unavailable_after_startup_timeout() {
+        let health = Health::start(Some("starting"));
+        let mut app = app(&health);
+
+        // Failing probes are retried while it's starting, backing off.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while app.world.resource::<HealthProbe>().retry < FIRST_RETRY * 4 {
+            assert!(Instant::now() < deadline, "not retried");
+            app.update();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*app.world.resource::<MeilisearchStatus>(), MeilisearchStatus::Starting);
+
+        let mut probe = app.world.resource_mut::<HealthProbe>();
+        probe.started = Instant::now() - STARTUP_TIMEOUT;
+        probe.next = Instant::now();
+        let MeilisearchStatus::Unavailable(e) = next_status(&mut app, &MeilisearchStatus::Starting) else {
+            panic!("not unavailable");
+        };
+        assert!(e.starts_with("Meilisearch is starting"), "{e}");
+    }
+
+    #[test]
+    fn crashes_once_ready() {
+        let health = Health::start(Some("available"));
+        let mut app = app(&health);
+        assert_eq!(next_status(&mut app, &MeilisearchStatus::Starting), MeilisearchStatus::Ready);
+
+        health.set(None);
+        // Instead of waiting for the next regular probe.
+        app.world.resource_mut::<HealthProbe>().next = Instant::now();
+        assert!(matches!(
+            next_status(&mut app, &MeilisearchStatus::Ready),
+            MeilisearchStatus::Crashed(_)
+        ));
+        // Probing backs off anew, to notice it coming back soon.
+        assert_eq!(app.world.resource::<HealthProbe>().retry, FIRST_RETRY);
+
+        health.set(Some("available"));
+        let crashed = app.world.resource::<MeilisearchStatus>().clone();
+        assert_eq!(next_status(&mut app, &crashed), MeilisearchStatus::Ready);
+    }
 }
@@ -1,56 +1,200 @@
-#![feature(exit_status_error)]
-
 //! Thin wrapper process (guard) for meilisearch.
-//! Listens for PDEATHSIG to determine when to kill meili and self.
+//! Listens for PDEATHSIG, SIGTERM and SIGINT to determine when to stop meili and self.
 //! Forwards all arguments after `--` to meilisearch.
 //!
-//! Kills meilisearch by dropping UnwindGuard.
-//! This happens either if this process panics
-//! *or* if the parent process exits *under any circumstance*.
-//! It does *not* kill meilisearch if meiliguard aborts.
+//! Restarts meilisearch when it exits, with exponential backoff,
+//! and gives up if it keeps exiting soon after starting.
+//! Reports this to Kumo on stdout, see `meiliguard::GuardReport`.
+//! Meilisearch writes its output to the guard's stderr.
+//!
+//! Stops meilisearch if the parent process exits *under any circumstance*,
+//! and by dropping UnwindGuard if this process panics.
+//! It does *not* stop meilisearch if meiliguard aborts.
+//!
+//! Signals aren't handled asynchronously, but blocked and read from a signalfd
+//! on the main thread, together with SIGCHLD for meilisearch exiting.
+//! Once stopped, the guard exits with meilisearch's status.
+//!
+//! Meilisearch is asked to exit with SIGTERM, so it can finish writing its database,
+//! and only killed if it hasn't exited after the grace period, see `meiliguard::grace_period`.
 
 #[cfg(unix)]
 mod inner {
-    use std::process::Child;
-    use std::sync::OnceLock;
+    use nix::poll::{PollFd, PollFlags};
+    use nix::sys::signal::Signal;
+    use nix::sys::signalfd::{SfdFlags, SigSet, SignalFd};
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{Child, ExitStatus};
+    use std::time::{Duration, Instant};
+
+    /// PDEATHSIG is mapped to SIGUSR1.
+    const EXIT_SIGNALS: [Signal; 3] = [Signal::SIGUSR1, Signal::SIGTERM, Signal::SIGINT];
+
+    /// The signals we handle, read on the main thread.
+    pub(super) struct Signals(SignalFd);
+
+    impl Signals {
+        fn mask() -> SigSet {
+            let mut mask = SigSet::empty();
+            for signal in EXIT_SIGNALS {
+                mask.add(signal);
+            }
+            mask.add(Signal::SIGCHLD);
+            mask
+        }
+
+        /// Blocks the signals, so they're only delivered through the signalfd.
+        /// Must be called before spawning threads, which would inherit the unblocked mask.
+        pub(super) fn new() -> Self {
+            let mask = Self::mask();
+            mask.thread_block().unwrap();
+            Self(SignalFd::with_flags(&mask, SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC).unwrap())
+        }
+
+        /// Unblocks the signals again.
+        /// Children inherit blocked signals, which would keep meilisearch from seeing SIGTERM.
+        /// Only does what's safe between fork and exec.
+        pub(super) fn unblock() -> std::io::Result<()> {
+            Self::mask().thread_unblock()?;
+            Ok(())
+        }
+
+        /// Waits for the next signal until the deadline, if any.
+        /// Returns `None` once the deadline passed.
+        fn next(&mut self, deadline: Option<Instant>) -> Option<Signal> {
+            loop {
+                if let Some(info) = self.0.read_signal().unwrap() {
+                    // Only blocked signals are read, so this shouldn't fail,
+                    // but `None` must only ever mean the deadline passed.
+                    match Signal::try_from(info.ssi_signo as i32) {
+                        Ok(signal) => return Some(signal),
+                        Err(_) => continue,
+                    }
+                }
+                let timeout = match deadline {
+                    Some(deadline) => {
+                        let left = deadline.saturating_duration_since(Instant::now());
+                        if left.is_zero() {
+                            return None;
+                        }
+                        // Rounded up, so we don't wake up just before the deadline.
+                        left.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32
+                    }
+                    None => -1,
+                };
+                let mut fds = [PollFd::new(self.0.as_raw_fd(), PollFlags::POLLIN)];
+                match nix::poll::poll(&mut fds, timeout) {
+                    Ok(_) | Err(nix::errno::Errno::EINTR) => {}
+                    Err(e) => panic!("cannot wait for signals: {e}"),
+                }
+            }
+        }
+
+        /// Waits until the deadline, or until we're asked to exit.
+        /// Returns whether we were.
+        pub(super) fn exit_before(&mut self, deadline: Instant) -> bool {
+            while let Some(signal) = self.next(Some(deadline)) {
+                if EXIT_SIGNALS.contains(&signal) {
+                    return true;
+                }
+            }
+            false
+        }
+    }
+
+    /// Waits for meilisearch to exit.
+    /// Once we're asked to exit, asks meilisearch to exit with SIGTERM,
+    /// and kills it if it takes longer than the grace period.
+    /// Returns its status, and whether we're exiting.
+    pub(super) fn wait(
+        child: &mut Child,
+        signals: &mut Signals,
+        grace_period: Duration,
+    ) -> (ExitStatus, bool) {
+        let mut deadline = None;
+        loop {
+            // SIGCHLD may have been pending since before the child was spawned,
+            // so its status is checked after every signal.
+            if let Some(status) = child.try_wait().unwrap() {
+                return (status, deadline.is_some());
+            }
+            match signals.next(deadline) {
+                Some(signal) if EXIT_SIGNALS.contains(&signal) && deadline.is_none() => {
+                    terminate(child);
+                    deadline = Some(Instant::now() + grace_period);
+                }
+                Some(_) => {}
+                None => {
+                    eprintln!("meiliguard: meilisearch didn't exit within {grace_period:?}, killing it");
+                    let _ = child.kill();
+                    return (child.wait().unwrap(), true);
+                }
+            }
+        }
+    }
 
-    pub(super) extern "C" fn handle_sigusr1(_: libc::c_int) {
+    fn terminate(child: &Child) {
         unsafe {
-            MEILI.take().unwrap();
+            libc::kill(child.id() as i32, libc::SIGTERM);
         }
     }
 
-    pub(super) static mut MEILI: OnceLock<UnwindGuard> = OnceLock::new();
+    /// Returns the code to exit with for meilisearch's status, like a shell would.
+    pub(super) fn exit_code(status: ExitStatus) -> i32 {
+        status.code().or(status.signal().map(|signal| 128 + signal)).unwrap_or(1)
+    }
 
+    /// Stops meilisearch if this process panics.
+    /// It polls, as the code that panicked may have been using the signalfd.
     #[derive(Debug)]
-    pub(super) struct UnwindGuard(pub(super) Child);
+    pub(super) struct UnwindGuard(pub(super) Child, pub(super) Duration);
 
     impl Drop for UnwindGuard {
         fn drop(&mut self) {
-            #[allow(unused_must_use)]
-            {
-                self.0.kill();
+            if !matches!(self.0.try_wait(), Ok(None)) {
+                return;
+            }
+            terminate(&self.0);
+            let deadline = Instant::now() + self.1;
+            while Instant::now() < deadline {
+                if !matches!(self.0.try_wait(), Ok(None)) {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(100));
             }
+            let _ = self.0.kill();
+            let _ = self.0.wait();
         }
     }
 }
 
+/// The delay before the first restart. It doubles with every crash in a row.
+#[cfg(unix)]
+const FIRST_RESTART_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+#[cfg(unix)]
+const MAX_RESTART_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+/// Meilisearch running at least this long didn't crash in a loop.
+#[cfg(unix)]
+const STABLE_RUN: std::time::Duration = std::time::Duration::from_secs(60);
+/// How often meilisearch may crash in a row before we give up.
+#[cfg(unix)]
+const MAX_CRASHES: u32 = 5;
+
 #[cfg(unix)]
 fn main() {
-    unsafe {
-        libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGUSR1);
-    }
+    use std::os::fd::AsFd;
+    use std::os::unix::process::CommandExt;
 
-    let sig_action = nix::sys::signal::SigAction::new(
-        nix::sys::signal::SigHandler::Handler(inner::handle_sigusr1),
-        nix::sys::signal::SaFlags::empty(),
-        nix::sys::signalfd::SigSet::empty(),
-    );
+    // Blocked before PDEATHSIG is set, so it can't kill us before meilisearch is stopped.
+    let mut signals = inner::Signals::new();
 
     unsafe {
-        nix::sys::signal::sigaction(nix::sys::signal::Signal::SIGUSR1, &sig_action).unwrap();
+        libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGUSR1);
     }
 
+    let grace_period = meiliguard::grace_period();
+
     let exe_path = std::env::current_exe().unwrap();
     let exe_dir = exe_path.parent().unwrap();
 
@@ -59,14 +203,61 @@ fn main() {
 
     let mut command = std::process::Command::new(meili_path);
     command.args(std::env::args().skip_while(|s| s != "--").skip(1));
-
+    // Our stdout is for reports.
+    let stderr = std::io::stderr().as_fd().try_clone_to_owned().unwrap();
+    command.stdout(std::process::Stdio::from(stderr));
     unsafe {
-        inner::MEILI
-            .set(inner::UnwindGuard(command.spawn().unwrap()))
-            .unwrap();
+        command.pre_exec(inner::Signals::unblock);
     }
 
-    std::thread::sleep(std::time::Duration::MAX);
+    let mut restarts = 0;
+    let mut crashes = 0;
+    loop {
+        let started = std::time::Instant::now();
+        // The executable may have been removed or replaced in the meantime, e.g. by an update.
+        let child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("meiliguard: cannot start meilisearch: {e}, giving up");
+                report(meiliguard::GuardReport::GaveUp);
+                std::process::exit(1);
+            }
+        };
+        let mut meili = inner::UnwindGuard(child, grace_period);
+        if restarts > 0 {
+            report(meiliguard::GuardReport::Restarted(restarts));
+        }
+
+        let (status, exiting) = inner::wait(&mut meili.0, &mut signals, grace_period);
+        if exiting {
+            std::process::exit(inner::exit_code(status));
+        }
+        report(meiliguard::GuardReport::Exited(status.to_string()));
+
+        crashes = if started.elapsed() >= STABLE_RUN { 1 } else { crashes + 1 };
+        if crashes > MAX_CRASHES {
+            eprintln!("meiliguard: meilisearch {status}, and exited {crashes} times in a row, giving up");
+            report(meiliguard::GuardReport::GaveUp);
+            std::process::exit(inner::exit_code(status));
+        }
+
+        let delay = (FIRST_RESTART_DELAY * 2u32.pow(crashes - 1)).min(MAX_RESTART_DELAY);
+        eprintln!("meiliguard: meilisearch {status}, restarting in {delay:?}");
+        if signals.exit_before(std::time::Instant::now() + delay) {
+            std::process::exit(inner::exit_code(status));
+        }
+        restarts += 1;
+    }
+}
+
+/// Writes the report for Kumo.
+/// Kumo may be gone, which the next PDEATHSIG tells us about.
+#[cfg(unix)]
+fn report(report: meiliguard::GuardReport) {
+    use std::io::Write;
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{report}");
+    let _ = stdout.flush();
 }
 
 #[cfg(windows)]
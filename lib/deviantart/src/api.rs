@@ -0,0 +1,414 @@
+//! Typed client for DeviantArt's REST API.
+//! All calls block, so within Bevy they should be run on a task pool.
+//! The base URL can be changed, so the client can be pointed at a local stand-in.
+
+use std::collections::VecDeque;
+
+mod model;
+
+pub use model::*;
+
+/// The base URL of DeviantArt's API.
+pub const API_URL: &str = "https://www.deviantart.com/api/v1/oauth2";
+
+/// The number of results requested per page.
+const PAGE_LIMIT: u32 = 24;
+
+/// How many deviations of a gallery [`Client::find_deviation`] looks through.
+const FIND_LIMIT: usize = 480;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request could not be sent, or the response could not be read.
+    Http(reqwest::Error),
+    /// DeviantArt responded with an error.
+    Api {
+        status: u16,
+        error: String,
+        description: Option<String>,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "{e}"),
+            Self::Api { status, error, description: Some(description) } => {
+                write!(f, "{error} ({status}): {description}")
+            }
+            Self::Api { status, error, description: None } => write!(f, "{error} ({status})"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A client authorized with an access token.
+/// Cloning is cheap, as the underlying connection pool is shared.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::blocking::Client,
+    base_url: String,
+    access_token: String,
+}
+
+impl Client {
+    pub fn new(access_token: impl Into<String>) -> Self {
+        Self::with_base_url(API_URL, access_token)
+    }
+
+    pub fn with_base_url(base_url: impl Into<String>, access_token: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::blocking::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_owned(),
+            access_token: access_token.into(),
+        }
+    }
+
+    fn get<T: serde::de::DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T> {
+        let response = self.http
+            .get(format!("{}{}", self.base_url, path))
+            .bearer_auth(&self.access_token)
+            .query(query)
+            .send()?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response.json()?);
+        }
+
+        Err(match response.json::<model::ErrorResponse>() {
+            Ok(body) => Error::Api {
+                status: status.as_u16(),
+                error: body.error,
+                description: body.error_description,
+            },
+            Err(_) => Error::Api {
+                status: status.as_u16(),
+                error: status.canonical_reason().unwrap_or("unknown").to_owned(),
+                description: None,
+            },
+        })
+    }
+
+    fn pages<T>(&self, path: impl Into<String>, query: Vec<(&'static str, String)>) -> Pages<T> {
+        Pages {
+            client: self.clone(),
+            path: path.into(),
+            query,
+            next: Some(Position::Start),
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// `/user/whoami`: The user this client is authorized as.
+    pub fn whoami(&self) -> Result<User> {
+        self.get("/user/whoami", &[])
+    }
+
+    /// `/deviation/{deviationid}`: A single deviation.
+    pub fn deviation(&self, deviation_id: &str) -> Result<Deviation> {
+        self.get(&format!("/deviation/{deviation_id}"), &[])
+    }
+
+    /// `/deviation/metadata`: Metadata of up to 50 deviations at once.
+    pub fn deviation_metadata(&self, deviation_ids: &[&str]) -> Result<Vec<DeviationMetadata>> {
+        let mut query = deviation_ids
+            .iter()
+            .map(|id| ("deviationids[]", id.to_string()))
+            .collect::<Vec<_>>();
+        query.push(("ext_submission", "false".to_owned()));
+
+        self.get::<model::MetadataResponse>("/deviation/metadata", &query)
+            .map(|response| response.metadata)
+    }
+
+    /// Finds the deviation with the ID from its URL, see [`crate::Link::Deviation`].
+    /// The API has no way to look those up, so this looks through the user's gallery,
+    /// up to its first few hundred deviations.
+    pub fn find_deviation(&self, username: &str, id: u64) -> Result<Option<Deviation>> {
+        for deviation in self.gallery(Some(username), "all").take(FIND_LIMIT) {
+            let deviation = deviation?;
+            let link = deviation.url.as_deref().map(crate::Link::parse);
+            if matches!(link, Some(Ok(crate::Link::Deviation { id: found, .. })) if found == id) {
+                return Ok(Some(deviation));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Downloads an image, like a deviation's preview.
+    /// Their URLs carry their own authorization.
+    pub fn download(&self, url: &str) -> Result<Vec<u8>> {
+        let response = self.http.get(url).send()?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Api {
+                status: status.as_u16(),
+                error: status.canonical_reason().unwrap_or("unknown").to_owned(),
+                description: None,
+            });
+        }
+        Ok(response.bytes()?.to_vec())
+    }
+
+    /// `/browse/home`: Recommended deviations.
+    pub fn browse_home(&self) -> Pages<Deviation> {
+        self.pages("/browse/home", Vec::new())
+    }
+
+    /// `/browse/tags`: Deviations with the given tag.
+    pub fn browse_tags(&self, tag: &str) -> Pages<Deviation> {
+        self.pages("/browse/tags", vec![("tag", tag.to_owned())])
+    }
+
+    /// `/gallery/{folderid}`: The deviations in a gallery folder.
+    /// Without a user, this is the gallery of the authorized user.
+    pub fn gallery(&self, username: Option<&str>, folder_id: &str) -> Pages<Deviation> {
+        let query = username
+            .map(|username| vec![("username", username.to_owned())])
+            .unwrap_or_default();
+        self.pages(format!("/gallery/{folder_id}"), query)
+    }
+
+    /// `/gallery/folders`: The gallery folders of a user.
+    /// Without a user, these are the folders of the authorized user.
+    pub fn gallery_folders(&self, username: Option<&str>) -> Pages<GalleryFolder> {
+        let mut query = vec![("calculate_size", "true".to_owned())];
+        query.extend(username.map(|username| ("username", username.to_owned())));
+        self.pages("/gallery/folders", query)
+    }
+
+    /// `/collections/folders`: The collection folders of a user.
+    /// Without a user, these are the folders of the authorized user.
+    pub fn collection_folders(&self, username: Option<&str>) -> Pages<CollectionFolder> {
+        let mut query = vec![("calculate_size", "true".to_owned())];
+        query.extend(username.map(|username| ("username", username.to_owned())));
+        self.pages("/collections/folders", query)
+    }
+}
+
+/// Where the next page starts.
+enum Position {
+    Start,
+    Offset(u32),
+    Cursor(String),
+}
+
+/// Iterates the results of a paginated endpoint, fetching pages as they are needed.
+/// After an error, the iterator ends.
+pub struct Pages<T> {
+    client: Client,
+    path: String,
+    query: Vec<(&'static str, String)>,
+    next: Option<Position>,
+    buffer: VecDeque<T>,
+}
+
+impl<T: serde::de::DeserializeOwned> Pages<T> {
+    /// Fetches the next page, and returns its results.
+    /// Returns `None` if there are no more pages.
+    pub fn next_page(&mut self) -> Option<Result<Vec<T>>> {
+        let position = self.next.take()?;
+
+        let mut query = self.query.clone();
+        query.push(("limit", PAGE_LIMIT.to_string()));
+        match position {
+            Position::Start => (),
+            Position::Offset(offset) => query.push(("offset", offset.to_string())),
+            Position::Cursor(cursor) => query.push(("cursor", cursor)),
+        }
+
+        let page = match self.client.get::<model::Page<T>>(&self.path, &query) {
+            Ok(page) => page,
+            Err(e) => return Some(Err(e)),
+        };
+
+        if page.has_more {
+            self.next = match (page.next_cursor, page.next_offset) {
+                (Some(cursor), _) => Some(Position::Cursor(cursor)),
+                (None, Some(offset)) => Some(Position::Offset(offset)),
+                (None, None) => None,
+            };
+        }
+
+        Some(Ok(page.results))
+    }
+}
+
+impl<T: serde::de::DeserializeOwned> Iterator for Pages<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.buffer.is_empty() {
+            match self.next_page()? {
+                Ok(results) => self.buffer.extend(results),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        self.buffer.pop_front().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockart::MockServer;
+
+    fn client(server: &MockServer) -> Client {
+        Client::with_base_url(server.api_url(), "mock-access-token")
+    }
+
+    fn folder(id: &str) -> String {
+        format!(r#"{{"folderid":"{id}","parent":null,"name":"Folder {id}","size":1}}"#)
+    }
+
+    fn page(results: &[&str], has_more: bool, next: &str) -> String {
+        let folders = results.iter().map(|id| folder(id)).collect::<Vec<_>>().join(",");
+        format!(r#"{{"results":[{folders}],"has_more":{has_more},{next}}}"#)
+    }
+
+    #[test]
+    fn get() {
+        let server = MockServer::start().unwrap();
+        server.set_api_response(
+            "/user/whoami",
+            200,
+            r#"{"userid":"1","username":"someone","usericon":"https://a.deviantart.net/icon.png","type":"regular"}"#,
+        );
+
+        let user = client(&server).whoami().unwrap();
+        assert_eq!(user.username, "someone");
+        assert_eq!(user.kind, "regular");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/api/v1/oauth2/user/whoami");
+        assert_eq!(requests[0].header("authorization"), Some("Bearer mock-access-token"));
+    }
+
+    #[test]
+    fn api_error() {
+        let server = MockServer::start().unwrap();
+        server.set_api_response(
+            "/deviation/missing",
+            400,
+            r#"{"status":"error","error":"invalid_request","error_description":"Deviation not found."}"#,
+        );
+
+        match client(&server).deviation("missing") {
+            Err(Error::Api { status, error, description }) => {
+                assert_eq!(status, 400);
+                assert_eq!(error, "invalid_request");
+                assert_eq!(description.as_deref(), Some("Deviation not found."));
+            }
+            other => panic!("expected an API error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn api_error_without_body() {
+        let server = MockServer::start().unwrap();
+        server.set_api_response("/user/whoami", 500, "not json");
+
+        match client(&server).whoami() {
+            Err(Error::Api { status: 500, description: None, .. }) => {}
+            other => panic!("expected an API error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn offset_pagination() {
+        let server = MockServer::start().unwrap();
+        let mut pages = client(&server).gallery_folders(Some("someone"));
+
+        server.set_api_response("/gallery/folders", 200, page(&["a", "b"], true, r#""next_offset":2"#));
+        let first = pages.next_page().unwrap().unwrap();
+        assert_eq!(first.iter().map(|folder| folder.folderid.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+
+        server.set_api_response("/gallery/folders", 200, page(&["c"], false, r#""next_offset":null"#));
+        let second = pages.next_page().unwrap().unwrap();
+        assert_eq!(second[0].folderid, "c");
+        assert!(pages.next_page().is_none());
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].query("username"), Some("someone"));
+        assert_eq!(requests[0].query("offset"), None);
+        assert_eq!(requests[1].query("offset"), Some("2"));
+        assert_eq!(requests[1].query("limit"), Some(PAGE_LIMIT.to_string().as_str()));
+    }
+
+    #[test]
+    fn cursor_pagination() {
+        let server = MockServer::start().unwrap();
+        let mut pages = client(&server).collection_folders(None);
+
+        server.set_api_response(
+            "/collections/folders",
+            200,
+            page(&["a"], true, r#""next_offset":1,"next_cursor":"abc""#),
+        );
+        pages.next_page().unwrap().unwrap();
+
+        server.set_api_response("/collections/folders", 200, page(&[], false, r#""next_cursor":null"#));
+        assert!(pages.next_page().unwrap().unwrap().is_empty());
+        assert!(pages.next_page().is_none());
+
+        let requests = server.requests();
+        // The cursor is preferred over the offset.
+        assert_eq!(requests[1].query("cursor"), Some("abc"));
+        assert_eq!(requests[1].query("offset"), None);
+    }
+
+    #[test]
+    fn iterator_ends_after_error() {
+        let server = MockServer::start().unwrap();
+        server.set_api_response("/browse/home", 429, r#"{"error":"rate_limited"}"#);
+
+        let mut pages = client(&server).browse_home();
+        assert!(matches!(pages.next(), Some(Err(Error::Api { status: 429, .. }))));
+        assert!(pages.next().is_none());
+    }
+
+    #[test]
+    fn find_deviation() {
+        let server = MockServer::start().unwrap();
+        let deviation = |uuid: &str, id: u64| {
+            format!(r#"{{"deviationid":"{uuid}","url":"https://www.deviantart.com/someone/art/title-{id}","title":"Title"}}"#)
+        };
+        let results = [deviation("first", 1), deviation("second", 2)].join(",");
+        server.set_api_response(
+            "/gallery/all",
+            200,
+            format!(r#"{{"results":[{results}],"has_more":false,"next_offset":null}}"#),
+        );
+
+        let client = client(&server);
+        let found = client.find_deviation("someone", 2).unwrap().unwrap();
+        assert_eq!(found.deviationid, "second");
+        assert!(client.find_deviation("someone", 3).unwrap().is_none());
+        assert_eq!(server.requests()[0].query("username"), Some("someone"));
+    }
+
+    #[test]
+    fn iterator_flattens_pages() {
+        let server = MockServer::start().unwrap();
+        server.set_api_response("/gallery/folders", 200, page(&["a", "b", "c"], false, r#""next_offset":null"#));
+
+        let ids = client(&server)
+            .gallery_folders(None)
+            .map(|folder| folder.unwrap().folderid)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,108 @@
+//! Models of the responses of DeviantArt's API.
+//! Only fields Kumo cares about are modelled; everything else is ignored.
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct User {
+    pub userid: String,
+    pub username: String,
+    pub usericon: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Image {
+    pub src: String,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub transparency: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Stats {
+    pub comments: u32,
+    pub favourites: u32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Deviation {
+    pub deviationid: String,
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<User>,
+    pub category: Option<String>,
+    /// Seconds since the Unix epoch, as a string.
+    pub published_time: Option<String>,
+    #[serde(default)]
+    pub is_mature: bool,
+    #[serde(default)]
+    pub is_deleted: bool,
+    pub stats: Option<Stats>,
+    pub preview: Option<Image>,
+    pub content: Option<Image>,
+    #[serde(default)]
+    pub thumbs: Vec<Image>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Tag {
+    pub tag_name: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DeviationMetadata {
+    pub deviationid: String,
+    pub title: String,
+    /// HTML.
+    pub description: String,
+    pub author: User,
+    pub license: String,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub is_favourited: bool,
+    #[serde(default)]
+    pub is_mature: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GalleryFolder {
+    pub folderid: String,
+    pub parent: Option<String>,
+    pub name: String,
+    /// Only present if the size was requested.
+    pub size: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CollectionFolder {
+    pub folderid: String,
+    pub name: String,
+    /// Only present if the size was requested.
+    pub size: Option<u32>,
+}
+
+/// A single page of a paginated endpoint.
+/// Depending on the endpoint, the next page is either given as offset or as cursor.
+#[derive(Deserialize, Debug)]
+pub(super) struct Page<T> {
+    pub(super) results: Vec<T>,
+    pub(super) has_more: bool,
+    pub(super) next_offset: Option<u32>,
+    pub(super) next_cursor: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(super) struct MetadataResponse {
+    pub(super) metadata: Vec<DeviationMetadata>,
+}
+
+/// The body DeviantArt responds with if a request failed.
+#[derive(Deserialize, Debug)]
+pub(super) struct ErrorResponse {
+    pub(super) error: String,
+    pub(super) error_description: Option<String>,
+}
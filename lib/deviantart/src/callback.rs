@@ -0,0 +1,141 @@
+//! Handling of the redirect DeviantArt sends the browser to after authorization.
+
+use std::io::{BufRead, Error, ErrorKind, Result, Write};
+use std::net::TcpStream;
+
+/// The outcome of a request to our redirect listener.
+pub(crate) enum Callback {
+    /// The user granted access, and the state matches ours.
+    Authorized(oauth2::AuthorizationCode),
+    /// The user denied access, or DeviantArt couldn't authorize us.
+    Denied {
+        error: String,
+        description: Option<String>,
+    },
+    /// The state is missing or doesn't match ours.
+    /// This request did not originate from our authorization request.
+    StateMismatch,
+    /// A request that is not a redirect at all, like the browser asking for a favicon.
+    Unrelated,
+}
+
+/// Reads the request from the stream and validates it against the expected state.
+pub(crate) fn read(stream: &TcpStream, expected_state: &oauth2::CsrfToken) -> Result<Callback> {
+    let mut reader = std::io::BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Closing the connection with the headers unread would reset it,
+    // and the browser might not show our response.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+        header.clear();
+    }
+
+    let redirect_url = request_line
+        .split_whitespace()
+        .nth(1)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "malformed request line"))?;
+    let url = oauth2::url::Url::parse(&("http://localhost".to_string() + redirect_url))
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+
+    let (code, error) = (param("code"), param("error"));
+
+    if code.is_none() && error.is_none() {
+        return Ok(Callback::Unrelated);
+    }
+
+    if param("state").as_deref() != Some(expected_state.secret().as_str()) {
+        return Ok(Callback::StateMismatch);
+    }
+
+    Ok(match (code, error) {
+        (_, Some(error)) => Callback::Denied {
+            error,
+            description: param("error_description"),
+        },
+        (Some(code), None) => Callback::Authorized(oauth2::AuthorizationCode::new(code)),
+        (None, None) => unreachable!(),
+    })
+}
+
+/// Writes a minimal HTML response to the stream.
+pub(crate) fn respond(stream: &mut TcpStream, status: &str, body: &str) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\ncontent-type: text/html; charset=utf-8\r\ncontent-length: {}\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes())
+}
+
+/// Returns a page that tells the user why authentication failed.
+pub(crate) fn error_page(message: &str) -> String {
+    format!(
+        "<!DOCTYPE html><title>Kumo</title><h1>Authentication failed</h1><p>{}</p>",
+        escape_html(message)
+    )
+}
+
+/// Escapes the given text, since it may come from the query string.
+fn escape_html(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    #[test]
+    fn idle_connection_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _idle = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _addr) = listener.accept().unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+
+        let state = oauth2::CsrfToken::new("state".to_owned());
+        let e = read(&stream, &state).err().expect("reading should time out");
+        assert!(matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut));
+    }
+
+    #[test]
+    fn reads_whole_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut browser = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        browser
+            .write_all(b"GET /?code=abc&state=state HTTP/1.1\r\nhost: localhost\r\naccept: */*\r\n\r\n")
+            .unwrap();
+        let (stream, _addr) = listener.accept().unwrap();
+
+        let state = oauth2::CsrfToken::new("state".to_owned());
+        let Callback::Authorized(code) = read(&stream, &state).unwrap() else {
+            panic!("expected an authorization code");
+        };
+        assert_eq!(code.secret(), "abc");
+
+        // Nothing is left unread, which would reset the connection on close.
+        stream.set_nonblocking(true).unwrap();
+        let mut rest = [0u8; 1];
+        assert_eq!(std::io::Read::read(&mut &stream, &mut rest).unwrap_err().kind(), ErrorKind::WouldBlock);
+    }
+}
@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+
+const AUTH_URL: &str = "https://www.deviantart.com/oauth2/authorize";
+
+const TOKEN_URL: &str = "https://www.deviantart.com/oauth2/token";
+
+/// The URLs Kumo talks to for authentication and API calls.
+/// They default to DeviantArt's, but can be pointed at a local mock server,
+/// in the settings or by configuring the plugin.
+/// The redirect URL defaults to localhost on the configured OAuth2 port.
+#[derive(Resource, Clone, Debug)]
+pub struct Endpoints {
+    pub auth_url: String,
+    pub token_url: String,
+    /// The redirect listener binds to the port of this URL on localhost.
+    pub redirect_url: String,
+    pub api_url: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            auth_url: AUTH_URL.to_owned(),
+            token_url: TOKEN_URL.to_owned(),
+            redirect_url: redirect_url(settings::Ports::default().oauth),
+            api_url: crate::api::API_URL.to_owned(),
+        }
+    }
+}
+
+impl Endpoints {
+    /// Returns the default endpoints for the given ports.
+    pub fn for_ports(ports: &settings::Ports) -> Self {
+        Self {
+            redirect_url: redirect_url(ports.oauth),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the endpoints configured in the settings, and the defaults for the others.
+    /// Configured URLs that can't be used are ignored.
+    pub fn configured(ports: &settings::Ports, configured: &settings::DeviantArtEndpoints) -> Self {
+        let default = Self::for_ports(ports);
+        let or = |name: &str, url: &Option<String>, check: fn(&str) -> Result<(), String>, default: String| {
+            match url.as_deref().map(|url| (url, check(url))) {
+                Some((url, Ok(()))) => url.to_owned(),
+                Some((url, Err(e))) => {
+                    warn!("Ignoring the DeviantArt {name} URL `{url}` from the settings: {e}");
+                    default
+                }
+                None => default,
+            }
+        };
+        Self {
+            auth_url: or("authorization", &configured.auth_url, Self::check_url, default.auth_url),
+            token_url: or("token", &configured.token_url, Self::check_url, default.token_url),
+            redirect_url: or(
+                "redirect",
+                &configured.redirect_url,
+                Self::check_redirect_url,
+                default.redirect_url,
+            ),
+            api_url: or("API", &configured.api_url, Self::check_url, default.api_url),
+        }
+    }
+
+    /// Checks that the URL can be used as endpoint.
+    pub fn check_url(url: &str) -> Result<(), String> {
+        oauth2::url::Url::parse(url).map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    /// Checks that the URL can be used as redirect URL, which needs a port to listen on.
+    pub fn check_redirect_url(url: &str) -> Result<(), String> {
+        redirect_port(url).map(|_| ())
+    }
+
+    /// Returns the port the redirect listener binds to.
+    pub(crate) fn redirect_port(&self) -> Result<u16, String> {
+        redirect_port(&self.redirect_url)
+    }
+}
+
+fn redirect_port(url: &str) -> Result<u16, String> {
+    oauth2::url::Url::parse(url)
+        .map_err(|e| e.to_string())?
+        .port_or_known_default()
+        .ok_or_else(|| format!("`{url}` has no port to listen on"))
+}
+
+pub(crate) fn redirect_url(port: u16) -> String {
+    format!("http://localhost:{port}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirect_urls() {
+        assert_eq!(redirect_port("http://localhost:11211"), Ok(11211));
+        assert_eq!(redirect_port("http://localhost"), Ok(80));
+        assert!(redirect_port("kumo://callback").is_err());
+        assert!(redirect_port("localhost:11211/callback").is_err());
+        assert!(Endpoints::check_redirect_url("not a url").is_err());
+    }
+
+    #[test]
+    fn configured() {
+        let ports = settings::Ports { oauth: 12345, ..Default::default() };
+        let configured = settings::DeviantArtEndpoints {
+            api_url: Some("http://127.0.0.1:8080/api".to_owned()),
+            // Unusable, so the defaults stay.
+            token_url: Some("nonsense".to_owned()),
+            redirect_url: Some("kumo://callback".to_owned()),
+            ..Default::default()
+        };
+        let endpoints = Endpoints::configured(&ports, &configured);
+        assert_eq!(endpoints.api_url, "http://127.0.0.1:8080/api");
+        assert_eq!(endpoints.auth_url, AUTH_URL);
+        assert_eq!(endpoints.token_url, TOKEN_URL);
+        assert_eq!(endpoints.redirect_url, "http://localhost:12345");
+        assert_eq!(endpoints.redirect_port(), Ok(12345));
+    }
+}
@@ -1,41 +1,172 @@
 use bevy::prelude::*;
 
 use std::net::TcpListener;
+use std::time::{Duration, Instant};
 
-const AUTH_URL: &str = "https://www.deviantart.com/oauth2/authorize";
+pub mod api;
+mod callback;
+mod endpoints;
+mod link;
+mod request;
+mod token;
 
-const TOKEN_URL: &str = "https://www.deviantart.com/oauth2/token";
+pub use endpoints::Endpoints;
+pub use link::{Folder, Link, LinkError};
 
-const REDIRECT_URL: &str = "http://localhost:11211";
-
-pub struct DeviantArtPlugin;
+#[derive(Default)]
+pub struct DeviantArtPlugin {
+    pub endpoints: Endpoints,
+}
 
 impl Plugin for DeviantArtPlugin {
     fn build(&self, app: &mut App) {
-        // Read our API credentials from file, for now.
-        let exe_path = std::env::current_exe().unwrap();
-        let exe_dir = exe_path.parent().unwrap();
-        let da_config = std::fs::read_to_string(exe_dir.join("deviantart.txt")).unwrap();
-        let (client_id, client_secret) = da_config.split_once('\n').unwrap();
+        app.insert_resource(self.endpoints.clone());
+
+        // Pick up the token of a previous session, if there is one.
+        // If it has expired in the meantime, `refresh_system` takes care of it.
+        match token::load() {
+            Ok(token) => {
+                info!("Loaded DeviantArt token from disk.");
+                app.insert_resource(TokenResponse(token));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => warn!("Failed to load DeviantArt token: {e}"),
+        }
+
+        app.add_state::<AuthState>();
+        if app.world.contains_resource::<TokenResponse>() {
+            app.world.resource_mut::<NextState<AuthState>>().set(AuthState::Authenticated);
+        }
+
+        app.add_event::<Authenticate>();
+        app.add_event::<Logout>();
+        app.add_event::<AuthenticationFailed>();
+
+        // The client is built from the credentials in the settings,
+        // and rebuilt whenever they, or the endpoints, change.
+        app.add_system(redirect_system.before(configure_system));
+        app.add_system(configure_system);
+        app.add_system(authenticate_system.after(configure_system));
+        app.add_system(logout_system.after(configure_system));
+
+        app.add_system(begin_authentication.in_schedule(OnEnter(AuthState::AwaitingBrowser)));
+        app.add_system(end_authentication.in_schedule(OnExit(AuthState::AwaitingBrowser)));
+        app.add_system(callback_system
+            .in_set(OnUpdate(AuthState::AwaitingBrowser))
+            .run_if(resource_exists::<OAuth2Client>()));
+        app.add_system(timeout_system.in_set(OnUpdate(AuthState::AwaitingBrowser)));
+
+        app.add_system(refresh_system
+            .in_set(OnUpdate(AuthState::Authenticated))
+            .run_if(resource_exists::<OAuth2Client>()));
+        app.add_system(poll_token_system.run_if(resource_exists::<request::TokenRequest>()));
+    }
+}
 
-        let client_id = oauth2::ClientId::new(client_id.to_owned());
-        let client_secret = oauth2::ClientSecret::new(client_secret.to_owned());
-        let auth_url = oauth2::AuthUrl::new(AUTH_URL.to_owned()).unwrap();
-        let token_url = oauth2::TokenUrl::new(TOKEN_URL.to_owned()).unwrap();
-        let redirect_url = oauth2::RedirectUrl::new(REDIRECT_URL.to_owned()).unwrap();
+/// The progress of authenticating with DeviantArt.
+/// Other plugins can react to it with `in_state` and `OnEnter`/`OnExit`.
+#[derive(States, Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum AuthState {
+    /// There is no token, or DeviantArt isn't configured.
+    #[default]
+    Unauthenticated,
+    /// The user is authorizing Kumo in the browser, and we listen for the redirect.
+    AwaitingBrowser,
+    /// The authorization code is being exchanged for a token.
+    Exchanging,
+    /// There is a token.
+    Authenticated,
+    /// The expired token is being refreshed.
+    Refreshing,
+    /// The last attempt to authenticate failed. See `AuthenticationFailed`.
+    Failed,
+}
 
-        let client = oauth2::basic::BasicClient::new(
-            client_id,
-            Some(client_secret),
-            auth_url,
-            Some(token_url)
-        ).set_redirect_uri(redirect_url);
+/// Moves the redirect to the OAuth2 port in the settings when it changes there,
+/// unless the redirect URL was configured explicitly.
+/// Authentication in progress keeps the redirect it started with.
+fn redirect_system(
+    settings: Res<settings::Settings>,
+    state: Res<State<AuthState>>,
+    ports: Option<ResMut<settings::Ports>>,
+    mut endpoints: ResMut<Endpoints>,
+    mut applied: Local<Option<u16>>,
+) {
+    if matches!(state.0, AuthState::AwaitingBrowser | AuthState::Exchanging) {
+        return;
+    }
+    let port = settings.ports.oauth;
+    // The port in use at startup may have been given on the command line,
+    // so only changes are applied.
+    match applied.replace(port) {
+        Some(previous) if previous != port => (),
+        _ => return,
+    }
+    let Ok(current) = endpoints.redirect_port() else {
+        return;
+    };
+    if endpoints.redirect_url != endpoints::redirect_url(current) {
+        return;
+    }
 
-        app.insert_resource(OAuth2Client(client));
-        app.add_system(start_system);
+    info!("Listening for DeviantArt's redirect on port {port} from now on.");
+    endpoints.redirect_url = endpoints::redirect_url(port);
+    if let Some(mut ports) = ports {
+        ports.oauth = port;
     }
 }
 
+/// Builds the OAuth2 client from the credentials in the settings.
+/// Without valid credentials, there is no client, and all authentication is off.
+fn configure_system(
+    mut commands: Commands,
+    settings: Res<settings::Settings>,
+    endpoints: Res<Endpoints>,
+    mut next_state: ResMut<NextState<AuthState>>,
+    mut applied: Local<Option<settings::DeviantArtSettings>>,
+) {
+    let credentials_changed = applied.as_ref() != Some(&settings.deviantart);
+    if !credentials_changed && !endpoints.is_changed() {
+        return;
+    }
+
+    // A token issued for other credentials is of no use to us.
+    if applied.is_some() && credentials_changed {
+        forget_token(&mut commands);
+        commands.remove_resource::<request::TokenRequest>();
+        next_state.set(AuthState::Unauthenticated);
+    }
+
+    *applied = Some(settings.deviantart.clone());
+
+    let (client_id, client_secret) = match settings.deviantart.credentials() {
+        Ok(credentials) => credentials,
+        Err(e) => {
+            warn!("{e}, DeviantArt is unavailable.");
+            commands.remove_resource::<OAuth2Client>();
+            return;
+        }
+    };
+
+    let client = oauth2_client(client_id, client_secret, &endpoints);
+    commands.insert_resource(OAuth2Client(client));
+}
+
+fn oauth2_client(client_id: &str, client_secret: &str, endpoints: &Endpoints) -> oauth2::basic::BasicClient {
+    let client_id = oauth2::ClientId::new(client_id.to_owned());
+    let client_secret = oauth2::ClientSecret::new(client_secret.to_owned());
+    let auth_url = oauth2::AuthUrl::new(endpoints.auth_url.clone()).unwrap();
+    let token_url = oauth2::TokenUrl::new(endpoints.token_url.clone()).unwrap();
+    let redirect_url = oauth2::RedirectUrl::new(endpoints.redirect_url.clone()).unwrap();
+
+    oauth2::basic::BasicClient::new(
+        client_id,
+        Some(client_secret),
+        auth_url,
+        Some(token_url)
+    ).set_redirect_uri(redirect_url)
+}
+
 #[derive(Resource)]
 struct OAuth2Client(oauth2::basic::BasicClient);
 
@@ -45,42 +176,186 @@ struct OAuth2Listener(TcpListener);
 #[derive(Resource)]
 struct CsrfToken(oauth2::CsrfToken);
 
+/// The PKCE verifier belonging to the challenge of the current authorization request.
+/// It lives and dies with `CsrfToken`.
+#[derive(Resource)]
+struct PkceVerifier(oauth2::PkceCodeVerifier);
+
+/// Present while Kumo is authenticated with DeviantArt.
 #[derive(Resource)]
-struct TokenResponse(oauth2::basic::BasicTokenResponse);
+pub struct TokenResponse(token::Token);
 
-fn start_system(
+impl TokenResponse {
+    /// Returns the scopes that were granted.
+    pub fn scopes(&self) -> Vec<String> {
+        self.0.scopes()
+    }
+
+    /// Returns an API client authorized with the current access token.
+    pub fn api_client(&self, endpoints: &Endpoints) -> api::Client {
+        api::Client::with_base_url(
+            &endpoints.api_url,
+            oauth2::TokenResponse::access_token(&self.0.response).secret(),
+        )
+    }
+}
+
+/// How long we wait for the browser to redirect back to us.
+const BROWSER_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// How long a connection to the redirect listener may take to send its request.
+const CALLBACK_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// When we stop waiting for the browser.
+#[derive(Resource)]
+struct BrowserDeadline(Instant);
+
+/// Opens DeviantArt's OAuth2 page in the browser and starts listening for its redirect.
+fn begin_authentication(
     mut commands: Commands,
-    client: Res<OAuth2Client>,
-    keys: Res<Input<KeyCode>>,
-    listener: Option<Res<OAuth2Listener>>,
-    csrf_token: Option<Res<CsrfToken>>,
+    client: Option<Res<OAuth2Client>>,
+    settings: Res<settings::Settings>,
+    endpoints: Res<Endpoints>,
+    mut next_state: ResMut<NextState<AuthState>>,
+    mut failures: EventWriter<AuthenticationFailed>,
 ) {
-    // Start the auth thingy.
+    let mut fail = |reason: String| {
+        warn!("{reason}");
+        failures.send(AuthenticationFailed(reason));
+        next_state.set(AuthState::Failed);
+    };
 
-    if keys.just_pressed(KeyCode::Tab) && listener.is_none() {
-        info!("Initializing authentication...");
+    // Without valid credentials, there is no client, see `configure_system`.
+    let Some(client) = client else {
+        let e = settings.deviantart.credentials().err().unwrap_or(settings::CredentialsError::NotConfigured);
+        return fail(e.to_string());
+    };
 
-        let (auth_url, csrf_token) = client.0
-            .authorize_url(oauth2::CsrfToken::new_random)
-            .add_scope(oauth2::Scope::new("browse".to_string()))
-            .url();
+    info!("Initializing authentication...");
+
+    let (pkce_challenge, pkce_verifier) = oauth2::PkceCodeChallenge::new_random_sha256();
+
+    let (auth_url, csrf_token) = client.0
+        .authorize_url(oauth2::CsrfToken::new_random)
+        .add_scope(oauth2::Scope::new("browse".to_string()))
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+
+    let port = match endpoints.redirect_port() {
+        Ok(port) => port,
+        Err(e) => return fail(format!("Cannot listen for DeviantArt's redirect: {e}")),
+    };
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(e) => return fail(format!("Failed to listen on port {port} for DeviantArt's redirect: {e}")),
+    };
+    listener.set_nonblocking(true).unwrap();
+
+    if let Err(e) = webbrowser::open(auth_url.as_str()) {
+        return fail(format!("To authenticate with DeviantArt, a browser is required: {e}"));
+    }
+
+    commands.insert_resource(OAuth2Listener(listener));
+    commands.insert_resource(CsrfToken(csrf_token));
+    commands.insert_resource(PkceVerifier(pkce_verifier));
+    commands.insert_resource(BrowserDeadline(Instant::now() + BROWSER_TIMEOUT));
+}
+
+/// Stops listening for DeviantArt's redirect and forgets the current authorization request.
+fn end_authentication(mut commands: Commands) {
+    commands.remove_resource::<OAuth2Listener>();
+    commands.remove_resource::<CsrfToken>();
+    commands.remove_resource::<PkceVerifier>();
+    commands.remove_resource::<BrowserDeadline>();
+}
+
+fn timeout_system(
+    deadline: Option<Res<BrowserDeadline>>,
+    mut next_state: ResMut<NextState<AuthState>>,
+    mut failures: EventWriter<AuthenticationFailed>,
+) {
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline.0) {
+        warn!("Timed out waiting for DeviantArt's redirect.");
+        failures.send(AuthenticationFailed("Timed out waiting for the browser".to_owned()));
+        next_state.set(AuthState::Failed);
+    }
+}
 
-        let listener = TcpListener::bind("127.0.0.1:11211").unwrap();
-        listener.set_nonblocking(true).unwrap();
+/// Replaces the current token, both in the world and on disk.
+fn store_token(commands: &mut Commands, token: token::Token) {
+    if let Err(e) = token::save(&token) {
+        error!("Failed to save DeviantArt token: {e}");
+    }
+    commands.insert_resource(TokenResponse(token));
+}
 
-        commands.insert_resource(OAuth2Listener(listener));
-        commands.insert_resource(CsrfToken(csrf_token));
+/// Send this to authenticate with DeviantArt in the browser.
+pub struct Authenticate;
+
+/// Send this to forget the current token,
+/// or to cancel authentication that is in progress.
+pub struct Logout;
+
+/// Returns why authentication can't start, if it can't.
+/// Starting it with `Authenticate` in these cases only logs the reason,
+/// or fails authentication if the credentials are missing.
+pub fn can_authenticate(state: AuthState, settings: &settings::DeviantArtSettings) -> Result<(), String> {
+    match state {
+        AuthState::Unauthenticated | AuthState::Failed => {
+            settings.credentials().map(|_| ()).map_err(|e| e.to_string())
+        }
+        AuthState::AwaitingBrowser | AuthState::Exchanging => {
+            Err("Already authenticating with DeviantArt".to_owned())
+        }
+        AuthState::Authenticated | AuthState::Refreshing => {
+            Err("Already authenticated with DeviantArt".to_owned())
+        }
+    }
+}
 
-        webbrowser::open(auth_url.as_str())
-            .expect("To authenticate with DeviantArt, a browser is required");
+fn authenticate_system(
+    state: Res<State<AuthState>>,
+    mut next_state: ResMut<NextState<AuthState>>,
+    mut events: EventReader<Authenticate>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+    match state.0 {
+        // Missing credentials are reported by `begin_authentication`.
+        AuthState::Unauthenticated | AuthState::Failed => next_state.set(AuthState::AwaitingBrowser),
+        AuthState::AwaitingBrowser | AuthState::Exchanging => info!("Already authenticating with DeviantArt."),
+        AuthState::Authenticated | AuthState::Refreshing => info!("Already authenticated with DeviantArt."),
     }
+}
 
-    // Do the auth thingy.
+fn logout_system(
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<AuthState>>,
+    mut events: EventReader<Logout>,
+) {
+    if events.iter().count() > 0 {
+        info!("Logging out of DeviantArt.");
+        forget_token(&mut commands);
+        commands.remove_resource::<request::TokenRequest>();
+        next_state.set(AuthState::Unauthenticated);
+    }
+}
 
+fn callback_system(
+    mut commands: Commands,
+    listener: Option<Res<OAuth2Listener>>,
+    csrf_token: Option<Res<CsrfToken>>,
+    pkce_verifier: Option<Res<PkceVerifier>>,
+    client: Res<OAuth2Client>,
+    mut next_state: ResMut<NextState<AuthState>>,
+    mut failures: EventWriter<AuthenticationFailed>,
+) {
     let (
         Some(listener),
-        Some(csrf_token)
-    ) = (listener, csrf_token) else {
+        Some(csrf_token),
+        Some(pkce_verifier)
+    ) = (listener, csrf_token, pkce_verifier) else {
         return;
     };
 
@@ -92,87 +367,304 @@ fn start_system(
 
     info!("Got stream.");
 
-    let code;
-    let state;
-
+    // Browsers may open connections ahead of time, which don't send a request until they're used.
+    // Reading blocks the main thread, so we don't wait long for them.
+    if let Err(e) = stream
+        .set_nonblocking(false)
+        .and_then(|_| stream.set_read_timeout(Some(CALLBACK_READ_TIMEOUT)))
     {
-        let mut reader = std::io::BufReader::new(&stream);
+        warn!("Failed to configure OAuth2 redirect stream: {e}");
+        return;
+    }
 
-        let mut request_line = String::new();
-        std::io::BufRead::read_line(&mut reader, &mut request_line).unwrap();
+    let code = match callback::read(&stream, &csrf_token.0) {
+        Ok(callback::Callback::Authorized(code)) => {
+            let message = "<script>close()</script>";
+            if let Err(e) = callback::respond(&mut stream, "200 OK", message) {
+                warn!("Failed to respond to OAuth2 redirect: {e}");
+            }
+            code
+        }
+        Ok(callback::Callback::Denied { error, description }) => {
+            let message = description.unwrap_or(error);
+            warn!("DeviantArt denied authorization: {message}");
+            failures.send(AuthenticationFailed(message.clone()));
+            let page = callback::error_page(&message);
+            if let Err(e) = callback::respond(&mut stream, "200 OK", &page) {
+                warn!("Failed to respond to OAuth2 redirect: {e}");
+            }
+            next_state.set(AuthState::Failed);
+            return;
+        }
+        Ok(callback::Callback::StateMismatch) => {
+            // This request wasn't caused by us, so we reject it,
+            // but keep waiting for the actual redirect.
+            warn!("Rejected OAuth2 redirect with unexpected state.");
+            let page = callback::error_page("The request did not originate from Kumo.");
+            if let Err(e) = callback::respond(&mut stream, "403 Forbidden", &page) {
+                warn!("Failed to respond to OAuth2 redirect: {e}");
+            }
+            return;
+        }
+        Ok(callback::Callback::Unrelated) => {
+            #[allow(unused_must_use)] {
+                callback::respond(&mut stream, "404 Not Found", "");
+            }
+            return;
+        }
+        Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {
+            info!("Dropped a connection to the OAuth2 redirect listener that sent no request.");
+            return;
+        }
+        Err(e) => {
+            warn!("Failed to read OAuth2 redirect: {e}");
+            #[allow(unused_must_use)] {
+                callback::respond(&mut stream, "400 Bad Request", "");
+            }
+            return;
+        }
+    };
 
-        let redirect_url = request_line.split_whitespace().nth(1).unwrap();
-        let url = oauth2::url::Url::parse(&("http://localhost".to_string() + redirect_url))
-            .unwrap();
+    // Exchange the code with a token.
+    // This happens in the background, see `poll_token_system`.
+    let client = client.0.clone();
+    let pkce_verifier = oauth2::PkceCodeVerifier::new(pkce_verifier.0.secret().clone());
+    let task = request::spawn(move || {
+        client
+            .exchange_code(code)
+            .set_pkce_verifier(pkce_verifier)
+            .request(oauth2::reqwest::http_client)
+    });
+    commands.insert_resource(request::TokenRequest {
+        task,
+        kind: request::TokenRequestKind::Exchange,
+    });
+    next_state.set(AuthState::Exchanging);
+}
 
-        let code_pair = url
-            .query_pairs()
-            .find(|pair| {
-                let (key, _) = pair;
-                key == "code"
-            })
-            .unwrap();
+/// How long to wait before retrying a refresh that failed for reasons
+/// other than DeviantArt rejecting the refresh token, e.g. no connection.
+const REFRESH_RETRY_DELAY: Duration = Duration::from_secs(30);
 
-        let (_, value) = code_pair;
-        code = oauth2::AuthorizationCode::new(value.into_owned());
+/// Present if the last refresh failed, and holds when to try again.
+#[derive(Resource)]
+struct RefreshRetry(Instant);
 
-        let state_pair = url
-            .query_pairs()
-            .find(|pair| {
-                let (key, _) = pair;
-                key == "state"
-            })
-            .unwrap();
+fn refresh_system(
+    mut commands: Commands,
+    client: Res<OAuth2Client>,
+    token: Option<Res<TokenResponse>>,
+    retry: Option<Res<RefreshRetry>>,
+    mut next_state: ResMut<NextState<AuthState>>,
+) {
+    let Some(token) = token else {
+        return;
+    };
 
-        let (_, value) = state_pair;
-        state = oauth2::CsrfToken::new(value.into_owned());
+    if !token.0.is_expired() || retry.is_some_and(|retry| Instant::now() < retry.0) {
+        return;
     }
 
-    let message = "<script>close()</script>";
+    let Some(refresh_token) = token.0.refresh_token().cloned() else {
+        info!("DeviantArt token expired and cannot be refreshed.");
+        forget_token(&mut commands);
+        next_state.set(AuthState::AwaitingBrowser);
+        return;
+    };
 
-    let response = format!(
-        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
-        message.len(),
-        message
-    );
-    std::io::Write::write_all(&mut stream, response.as_bytes()).unwrap();
+    info!("Refreshing DeviantArt token...");
 
-    println!(
-        "DeviantArt returned the following code:\n{}\n",
-        code.secret()
-    );
-    println!(
-        "DeviantArt returned the following state:\n{} (expected `{}`)\n",
-        state.secret(),
-        csrf_token.0.secret()
-    );
+    let client = client.0.clone();
+    let task = {
+        let refresh_token = refresh_token.clone();
+        request::spawn(move || {
+            client
+                .exchange_refresh_token(&refresh_token)
+                .request(oauth2::reqwest::http_client)
+        })
+    };
+    commands.remove_resource::<RefreshRetry>();
+    commands.insert_resource(request::TokenRequest {
+        task,
+        kind: request::TokenRequestKind::Refresh(refresh_token),
+    });
+    next_state.set(AuthState::Refreshing);
+}
 
-    // Exchange the code with a token.
-    let token_res = client.0
-        .exchange_code(code)
-        .request(oauth2::reqwest::http_client);
-
-    println!(
-        "DeviantArt returned the following token:\n{:?}\n",
-        token_res
-    );
-
-    if let Ok(token) = token_res {
-        let scopes = if let Some(scopes_vec) = oauth2::TokenResponse::scopes(&token) {
-            scopes_vec
-                .iter()
-                .flat_map(|comma_separated| comma_separated.split(','))
-                .collect::<Vec<_>>()
-        } else {
-            Vec::new()
-        };
-        println!("DeviantArt returned the following scopes:\n{:?}\n", scopes);
+/// Sent when authenticating with DeviantArt or refreshing the token failed.
+pub struct AuthenticationFailed(pub String);
+
+fn poll_token_system(
+    mut commands: Commands,
+    mut token_request: ResMut<request::TokenRequest>,
+    mut next_state: ResMut<NextState<AuthState>>,
+    mut failures: EventWriter<AuthenticationFailed>,
+) {
+    let Some(token_res) = request::poll(&mut token_request.task) else {
+        return;
+    };
+
+    commands.remove_resource::<request::TokenRequest>();
+
+    match (token_res, &token_request.kind) {
+        (Ok(token), request::TokenRequestKind::Exchange) => {
+            let token = token::Token::new(token);
+            info!("Authenticated with DeviantArt, granted scopes: {:?}", token.scopes());
+            store_token(&mut commands, token);
+            next_state.set(AuthState::Authenticated);
+        }
+        (Err(e), request::TokenRequestKind::Exchange) => {
+            warn!("Failed to exchange DeviantArt authorization code: {e}");
+            failures.send(AuthenticationFailed(e.to_string()));
+            next_state.set(AuthState::Failed);
+        }
+        (Ok(mut token), request::TokenRequestKind::Refresh(refresh_token)) => {
+            info!("Refreshed DeviantArt token.");
+            // Keep the old refresh token if DeviantArt didn't issue a new one.
+            if oauth2::TokenResponse::refresh_token(&token).is_none() {
+                token.set_refresh_token(Some(refresh_token.clone()));
+            }
+            store_token(&mut commands, token::Token::new(token));
+            next_state.set(AuthState::Authenticated);
+        }
+        (Err(oauth2::RequestTokenError::ServerResponse(e)), request::TokenRequestKind::Refresh(_)) => {
+            // The refresh token was rejected, so only the browser can help us now.
+            warn!("DeviantArt rejected the refresh token: {e}");
+            failures.send(AuthenticationFailed(e.to_string()));
+            forget_token(&mut commands);
+            next_state.set(AuthState::AwaitingBrowser);
+        }
+        (Err(e), request::TokenRequestKind::Refresh(_)) => {
+            warn!("Failed to refresh DeviantArt token: {e}");
+            failures.send(AuthenticationFailed(e.to_string()));
+            commands.insert_resource(RefreshRetry(Instant::now() + REFRESH_RETRY_DELAY));
+            next_state.set(AuthState::Authenticated);
+        }
+    }
+}
 
-        commands.insert_resource(TokenResponse(token));
+/// Removes the current token, both from the world and from disk.
+fn forget_token(commands: &mut Commands) {
+    if let Err(e) = token::remove() {
+        error!("Failed to remove DeviantArt token: {e}");
     }
+    commands.remove_resource::<TokenResponse>();
+}
 
-    // Finally, clean up the auth thingy.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockart::{Authorize, MockServer};
+
+    fn endpoints(server: &MockServer, redirect_port: u16) -> Endpoints {
+        Endpoints {
+            auth_url: server.auth_url(),
+            token_url: server.token_url(),
+            redirect_url: format!("http://127.0.0.1:{redirect_port}"),
+            api_url: server.api_url(),
+        }
+    }
 
-    commands.remove_resource::<OAuth2Listener>();
-    commands.remove_resource::<CsrfToken>();
-}
\ No newline at end of file
+    /// Authorizes in mockart's stand-in browser, and reads its redirect like `callback_system`.
+    fn authorize(server: &MockServer, client: &oauth2::basic::BasicClient, listener: &TcpListener) -> callback::Callback {
+        let (auth_url, csrf_token) = client
+            .authorize_url(oauth2::CsrfToken::new_random)
+            .add_scope(oauth2::Scope::new("browse".to_string()))
+            .url();
+
+        std::thread::scope(|scope| {
+            let browser = scope.spawn(|| server.follow(auth_url.as_str()).unwrap());
+            let (mut stream, _addr) = listener.accept().unwrap();
+            let callback = callback::read(&stream, &csrf_token).unwrap();
+            callback::respond(&mut stream, "200 OK", "").unwrap();
+            drop(stream);
+            assert_eq!(browser.join().unwrap().status, 200);
+            callback
+        })
+    }
+
+    #[test]
+    fn exchange_and_refresh() {
+        let server = MockServer::start().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoints = endpoints(&server, listener.local_addr().unwrap().port());
+        let client = oauth2_client("client-id", "client-secret", &endpoints);
+
+        let callback::Callback::Authorized(code) = authorize(&server, &client, &listener) else {
+            panic!("expected an authorization code");
+        };
+        assert_eq!(code.secret(), "mock-code");
+
+        let (_challenge, verifier) = oauth2::PkceCodeChallenge::new_random_sha256();
+        let verifier_secret = verifier.secret().clone();
+        let response = client
+            .exchange_code(code)
+            .set_pkce_verifier(verifier)
+            .request(oauth2::reqwest::http_client)
+            .unwrap();
+        let token = token::Token::new(response);
+        assert_eq!(oauth2::TokenResponse::access_token(&token.response).secret(), "mock-access-token");
+        assert_eq!(token.scopes(), ["browse"]);
+        assert!(!token.is_expired());
+
+        server.set_token_response(
+            200,
+            r#"{"status":"success","access_token":"refreshed-access-token","token_type":"Bearer","expires_in":3600,"refresh_token":"new-refresh-token","scope":"browse"}"#,
+        );
+        let response = client
+            .exchange_refresh_token(token.refresh_token().unwrap())
+            .request(oauth2::reqwest::http_client)
+            .unwrap();
+        assert_eq!(oauth2::TokenResponse::access_token(&response).secret(), "refreshed-access-token");
+
+        let token_requests = server
+            .requests()
+            .into_iter()
+            .filter(|request| request.path == "/oauth2/token")
+            .collect::<Vec<_>>();
+        assert_eq!(token_requests.len(), 2);
+        let exchange = &token_requests[0].body;
+        assert!(exchange.contains("grant_type=authorization_code"));
+        assert!(exchange.contains("code=mock-code"));
+        assert!(exchange.contains(&format!("code_verifier={verifier_secret}")));
+        let refresh = &token_requests[1].body;
+        assert!(refresh.contains("grant_type=refresh_token"));
+        assert!(refresh.contains("refresh_token=mock-refresh-token"));
+    }
+
+    #[test]
+    fn denied() {
+        let server = MockServer::start().unwrap();
+        server.set_authorize(Authorize::Deny {
+            error: "access_denied".to_owned(),
+            description: "The user denied access".to_owned(),
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoints = endpoints(&server, listener.local_addr().unwrap().port());
+        let client = oauth2_client("client-id", "client-secret", &endpoints);
+
+        match authorize(&server, &client, &listener) {
+            callback::Callback::Denied { error, description } => {
+                assert_eq!(error, "access_denied");
+                assert_eq!(description.as_deref(), Some("The user denied access"));
+            }
+            _ => panic!("expected a denial"),
+        }
+    }
+
+    #[test]
+    fn rejected_refresh_token() {
+        let server = MockServer::start().unwrap();
+        server.set_token_response(
+            400,
+            r#"{"error":"invalid_grant","error_description":"The refresh token is invalid."}"#,
+        );
+        let client = oauth2_client("client-id", "client-secret", &endpoints(&server, 1));
+
+        let result = client
+            .exchange_refresh_token(&oauth2::RefreshToken::new("stale".to_owned()))
+            .request(oauth2::reqwest::http_client);
+        // `poll_token_system` tells these apart from connection failures, to start over in the browser.
+        assert!(matches!(result, Err(oauth2::RequestTokenError::ServerResponse(_))));
+    }
+}
@@ -0,0 +1,243 @@
+//! Links to deviantart.com, as they appear in the browser.
+//! Note that the numeric IDs in these URLs are not the IDs the API uses.
+
+use oauth2::url::Url;
+
+/// Paths on deviantart.com that look like user profiles, but aren't.
+const RESERVED: &[&str] = &[
+    "about", "api", "core-membership", "daily-deviations", "developers", "join", "notifications",
+    "oauth2", "popular", "search", "settings", "shop", "tag", "team", "topic", "users", "watch",
+];
+
+/// A page on deviantart.com Kumo can open.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Link {
+    /// `deviantart.com/{username}/art/{title}-{id}`
+    Deviation { username: String, title: String, id: u64 },
+    /// `deviantart.com/{username}/gallery[/{id}/{name}]`
+    Gallery { username: String, folder: Option<Folder> },
+    /// `deviantart.com/{username}/favourites[/{id}/{name}]`
+    Collection { username: String, folder: Option<Folder> },
+    /// `deviantart.com/{username}`
+    User { username: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Folder {
+    pub id: u64,
+    /// The name as it appears in the URL, e.g. `my-folder`.
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    InvalidUrl(String),
+    NotDeviantArt,
+    Unsupported,
+}
+
+impl std::fmt::Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidUrl(e) => write!(f, "Invalid URL: {e}"),
+            Self::NotDeviantArt => write!(f, "Not a deviantart.com URL"),
+            Self::Unsupported => write!(f, "Kumo can't open this page of deviantart.com"),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+impl Link {
+    /// Parses a deviantart.com URL.
+    /// Besides `http` and `https`, the `kumo` scheme is accepted,
+    /// which Kumo registers as URL handler.
+    /// The old `{username}.deviantart.com` form is accepted, too.
+    pub fn parse(url: &str) -> Result<Self, LinkError> {
+        let url = Url::parse(url).map_err(|e| LinkError::InvalidUrl(e.to_string()))?;
+        if !matches!(url.scheme(), "http" | "https" | "kumo") {
+            return Err(LinkError::NotDeviantArt);
+        }
+
+        let host = url.host_str().ok_or(LinkError::NotDeviantArt)?.to_ascii_lowercase();
+        let mut segments = Vec::new();
+        match host.strip_suffix("deviantart.com") {
+            Some("" | "www.") => (),
+            Some(subdomain) => match subdomain.strip_suffix('.') {
+                Some(username) if !username.contains('.') => segments.push(username.to_owned()),
+                _ => return Err(LinkError::NotDeviantArt),
+            },
+            None => return Err(LinkError::NotDeviantArt),
+        }
+        segments.extend(
+            url.path_segments()
+                .into_iter()
+                .flatten()
+                .filter(|segment| !segment.is_empty())
+                .map(str::to_owned),
+        );
+
+        let Some((username, rest)) = segments.split_first() else {
+            return Err(LinkError::Unsupported);
+        };
+        if !is_username(username) {
+            return Err(LinkError::Unsupported);
+        }
+        let username = username.clone();
+
+        match rest.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] => Ok(Self::User { username }),
+            ["art", slug] => {
+                let (title, id) = slug.rsplit_once('-').ok_or(LinkError::Unsupported)?;
+                let id = id.parse().map_err(|_| LinkError::Unsupported)?;
+                Ok(Self::Deviation { username, title: title.replace('-', " "), id })
+            }
+            ["gallery", ref folder @ ..] => Ok(Self::Gallery { username, folder: folder_of(folder)? }),
+            ["favourites", ref folder @ ..] => Ok(Self::Collection { username, folder: folder_of(folder)? }),
+            _ => Err(LinkError::Unsupported),
+        }
+    }
+
+    pub fn username(&self) -> &str {
+        match self {
+            Self::Deviation { username, .. }
+            | Self::Gallery { username, .. }
+            | Self::Collection { username, .. }
+            | Self::User { username } => username,
+        }
+    }
+
+    /// Returns the page on deviantart.com.
+    pub fn url(&self) -> String {
+        let base = format!("https://www.deviantart.com/{}", self.username());
+        let folder_url = |kind: &str, folder: &Option<Folder>| match folder {
+            Some(folder) if folder.name.is_empty() => format!("{base}/{kind}/{}", folder.id),
+            Some(folder) => format!("{base}/{kind}/{}/{}", folder.id, folder.name),
+            None => format!("{base}/{kind}"),
+        };
+        match self {
+            Self::Deviation { title, id, .. } => format!("{base}/art/{}-{id}", title.replace(' ', "-")),
+            Self::Gallery { folder, .. } => folder_url("gallery", folder),
+            Self::Collection { folder, .. } => folder_url("favourites", folder),
+            Self::User { .. } => base.clone(),
+        }
+    }
+}
+
+fn is_username(segment: &str) -> bool {
+    !RESERVED.contains(&segment.to_ascii_lowercase().as_str())
+        && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Returns the folder of a gallery or collection path, if it's not all of them.
+fn folder_of(segments: &[&str]) -> Result<Option<Folder>, LinkError> {
+    match segments {
+        [] | ["all"] => Ok(None),
+        [id, rest @ ..] if rest.len() <= 1 => Ok(Some(Folder {
+            id: id.parse().map_err(|_| LinkError::Unsupported)?,
+            name: rest.first().copied().unwrap_or_default().to_owned(),
+        })),
+        _ => Err(LinkError::Unsupported),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(username: &str) -> Link {
+        Link::User { username: username.to_owned() }
+    }
+
+    fn folder(id: u64, name: &str) -> Option<Folder> {
+        Some(Folder { id, name: name.to_owned() })
+    }
+
+    #[test]
+    fn deviation() {
+        assert_eq!(
+            Link::parse("https://www.deviantart.com/someone/art/Clouds-Over-Water-123456"),
+            Ok(Link::Deviation { username: "someone".to_owned(), title: "Clouds Over Water".to_owned(), id: 123456 }),
+        );
+        assert_eq!(
+            Link::parse("https://www.deviantart.com/someone/art/no-id"),
+            Err(LinkError::Unsupported),
+        );
+    }
+
+    #[test]
+    fn gallery_and_collection() {
+        assert_eq!(
+            Link::parse("https://www.deviantart.com/someone/gallery"),
+            Ok(Link::Gallery { username: "someone".to_owned(), folder: None }),
+        );
+        assert_eq!(
+            Link::parse("https://www.deviantart.com/someone/gallery/all"),
+            Ok(Link::Gallery { username: "someone".to_owned(), folder: None }),
+        );
+        assert_eq!(
+            Link::parse("https://www.deviantart.com/someone/gallery/42/my-folder"),
+            Ok(Link::Gallery { username: "someone".to_owned(), folder: folder(42, "my-folder") }),
+        );
+        assert_eq!(
+            Link::parse("https://www.deviantart.com/someone/favourites/42"),
+            Ok(Link::Collection { username: "someone".to_owned(), folder: folder(42, "") }),
+        );
+        assert_eq!(
+            Link::parse("https://www.deviantart.com/someone/gallery/not-an-id/my-folder"),
+            Err(LinkError::Unsupported),
+        );
+    }
+
+    #[test]
+    fn user_profile() {
+        assert_eq!(Link::parse("https://www.deviantart.com/someone"), Ok(user("someone")));
+        assert_eq!(Link::parse("https://deviantart.com/someone/"), Ok(user("someone")));
+        assert_eq!(Link::parse("https://www.deviantart.com/"), Err(LinkError::Unsupported));
+    }
+
+    #[test]
+    fn subdomain() {
+        assert_eq!(Link::parse("https://someone.deviantart.com"), Ok(user("someone")));
+        assert_eq!(
+            Link::parse("http://someone.deviantart.com/gallery"),
+            Ok(Link::Gallery { username: "someone".to_owned(), folder: None }),
+        );
+        assert_eq!(Link::parse("https://a.b.deviantart.com"), Err(LinkError::NotDeviantArt));
+    }
+
+    #[test]
+    fn kumo_scheme() {
+        assert_eq!(Link::parse("kumo://www.deviantart.com/someone"), Ok(user("someone")));
+        assert_eq!(Link::parse("ftp://www.deviantart.com/someone"), Err(LinkError::NotDeviantArt));
+    }
+
+    #[test]
+    fn reserved_paths() {
+        for path in ["search", "Tag", "settings", "oauth2"] {
+            let url = format!("https://www.deviantart.com/{path}");
+            assert_eq!(Link::parse(&url), Err(LinkError::Unsupported), "{url}");
+        }
+        assert_eq!(Link::parse("https://www.deviantart.com/some_one"), Err(LinkError::Unsupported));
+    }
+
+    #[test]
+    fn foreign_hosts() {
+        assert_eq!(Link::parse("https://example.com/someone"), Err(LinkError::NotDeviantArt));
+        assert_eq!(Link::parse("https://notdeviantart.com/someone"), Err(LinkError::NotDeviantArt));
+        assert_eq!(Link::parse("https://deviantart.com.example.com/someone"), Err(LinkError::NotDeviantArt));
+        assert!(matches!(Link::parse("not a url"), Err(LinkError::InvalidUrl(_))));
+    }
+
+    #[test]
+    fn url_round_trip() {
+        for url in [
+            "https://www.deviantart.com/someone",
+            "https://www.deviantart.com/someone/art/Clouds-123456",
+            "https://www.deviantart.com/someone/gallery/42/my-folder",
+            "https://www.deviantart.com/someone/favourites",
+        ] {
+            assert_eq!(Link::parse(url).unwrap().url(), url);
+        }
+    }
+}
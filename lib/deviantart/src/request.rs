@@ -0,0 +1,36 @@
+//! DeviantArt's HTTP calls block until they complete, which may take a while
+//! on slow connections. To keep the window responsive, they are run on the
+//! async compute task pool, and polled by systems until they are done.
+
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+
+pub(crate) type TokenResult = Result<
+    oauth2::basic::BasicTokenResponse,
+    oauth2::basic::BasicRequestTokenError<oauth2::reqwest::HttpClientError>,
+>;
+
+/// A token request in flight.
+/// There is at most one at any time.
+#[derive(Resource)]
+pub(crate) struct TokenRequest {
+    pub(crate) task: Task<TokenResult>,
+    pub(crate) kind: TokenRequestKind,
+}
+
+pub(crate) enum TokenRequestKind {
+    /// Exchanging an authorization code for a token.
+    Exchange,
+    /// Refreshing the token with the given refresh token.
+    Refresh(oauth2::RefreshToken),
+}
+
+/// Runs the given blocking HTTP call on the async compute task pool.
+pub(crate) fn spawn<T: Send + 'static>(call: impl FnOnce() -> T + Send + 'static) -> Task<T> {
+    AsyncComputeTaskPool::get().spawn(async move { call() })
+}
+
+/// Returns the output of the task if it is done, without blocking otherwise.
+pub(crate) fn poll<T>(task: &mut Task<T>) -> Option<T> {
+    futures_lite::future::block_on(futures_lite::future::poll_once(task))
+}
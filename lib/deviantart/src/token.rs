@@ -0,0 +1,82 @@
+//! Persistence for DeviantArt's OAuth2 tokens.
+//! The token is stored in Kumo's config directory, next to the settings.
+//! On Unix, the file is only readable and writable by the current user.
+
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Access tokens are refreshed this long before they actually expire,
+/// so that requests in flight don't race the expiration.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Token {
+    pub(crate) response: oauth2::basic::BasicTokenResponse,
+    /// Seconds since the Unix epoch at which the access token expires.
+    /// `None` if DeviantArt didn't tell us.
+    expires_at: Option<u64>,
+}
+
+impl Token {
+    pub(crate) fn new(response: oauth2::basic::BasicTokenResponse) -> Self {
+        let expires_at = oauth2::TokenResponse::expires_in(&response)
+            .map(|expires_in| (SystemTime::now() + expires_in).duration_since(UNIX_EPOCH))
+            .and_then(|expires_at| expires_at.ok())
+            .map(|expires_at| expires_at.as_secs());
+
+        Self { response, expires_at }
+    }
+
+    /// Returns whether the access token has expired, or is about to.
+    pub(crate) fn is_expired(&self) -> bool {
+        let Some(expires_at) = self.expires_at else {
+            return false;
+        };
+        let expires_at = UNIX_EPOCH + Duration::from_secs(expires_at);
+        SystemTime::now() + EXPIRY_MARGIN >= expires_at
+    }
+
+    /// Returns the scopes that were granted.
+    pub(crate) fn scopes(&self) -> Vec<String> {
+        if let Some(scopes_vec) = oauth2::TokenResponse::scopes(&self.response) {
+            scopes_vec
+                .iter()
+                .flat_map(|comma_separated| comma_separated.split(','))
+                .map(str::to_owned)
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub(crate) fn refresh_token(&self) -> Option<&oauth2::RefreshToken> {
+        oauth2::TokenResponse::refresh_token(&self.response)
+    }
+}
+
+/// Returns the path to the token file.
+fn path() -> PathBuf {
+    settings::config_dir().join("deviantart.token")
+}
+
+/// Loads the token from disk.
+pub(crate) fn load() -> Result<Token> {
+    let bytes = std::fs::read(path())?;
+    serde_json::from_slice(&bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Writes the token to disk, replacing any previous token.
+pub(crate) fn save(token: &Token) -> Result<()> {
+    let bytes = serde_json::to_vec(token).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    settings::write_private(path(), &bytes)
+}
+
+/// Removes the token from disk, if there is one.
+pub(crate) fn remove() -> Result<()> {
+    match std::fs::remove_file(path()) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
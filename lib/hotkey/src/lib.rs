@@ -0,0 +1,147 @@
+//! The global hotkey that shows and hides the window, even while Kumo isn't focused.
+//!
+//! On X11, the shortcut is grabbed on the root window. On Wayland, programs can't
+//! grab keys, so the shortcut is bound through the desktop portal, if the desktop has one.
+//! Without either, `kumo toggle` does the same, and can be bound to a shortcut
+//! in the desktop's settings.
+
+use bevy::prelude::*;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Mutex;
+
+#[cfg(target_os = "linux")]
+mod portal;
+mod shortcut;
+#[cfg(target_os = "linux")]
+mod x11;
+
+pub use shortcut::{Key, Modifiers, Shortcut};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    X11,
+    /// The desktop portal's GlobalShortcuts interface.
+    Portal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HotkeyStatus {
+    Disabled,
+    /// A backend is binding the shortcut.
+    Starting(Backend),
+    Active(Backend),
+    /// No backend could bind the shortcut.
+    Unavailable,
+}
+
+/// What the backends' threads report.
+pub(crate) enum Message {
+    Active(Backend),
+    /// The backend can't bind the shortcut, or stopped working.
+    Unavailable(Backend, String),
+    Pressed,
+}
+
+#[derive(Resource)]
+pub struct Hotkey {
+    pub status: HotkeyStatus,
+    shortcut: Option<Shortcut>,
+    /// The backends left to try, in order.
+    backends: Vec<Backend>,
+    /// The backends' threads stop once they can't send anymore, i.e. once Kumo exits.
+    sender: Sender<Message>,
+    receiver: Mutex<Receiver<Message>>,
+}
+
+impl Hotkey {
+    fn new(settings: &settings::HotkeySettings) -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut hotkey = Self {
+            status: HotkeyStatus::Disabled,
+            shortcut: None,
+            backends: backends(),
+            sender,
+            receiver: Mutex::new(receiver),
+        };
+        if !settings.enabled {
+            return hotkey;
+        }
+
+        match Shortcut::parse(&settings.shortcut) {
+            Ok(shortcut) => {
+                hotkey.shortcut = Some(shortcut);
+                hotkey.start_next();
+            }
+            Err(e) => {
+                warn!("Invalid hotkey `{}`: {e}", settings.shortcut);
+                hotkey.status = HotkeyStatus::Unavailable;
+            }
+        }
+        hotkey
+    }
+
+    /// Starts the next backend, if there's one left.
+    fn start_next(&mut self) {
+        let Some(shortcut) = self.shortcut.clone() else {
+            return;
+        };
+        if self.backends.is_empty() {
+            warn!("No global hotkey is available; bind `kumo toggle` to {shortcut} in your desktop's settings instead");
+            self.status = HotkeyStatus::Unavailable;
+            return;
+        }
+
+        let backend = self.backends.remove(0);
+        self.status = HotkeyStatus::Starting(backend);
+        #[cfg(target_os = "linux")]
+        match backend {
+            Backend::X11 => x11::listen(shortcut, self.sender.clone()),
+            Backend::Portal => portal::listen(shortcut, self.sender.clone()),
+        }
+    }
+}
+
+/// Returns the backends that may work in this session, in the order they're tried.
+fn backends() -> Vec<Backend> {
+    if !cfg!(target_os = "linux") {
+        return Vec::new();
+    }
+    let has = |var: &str| std::env::var_os(var).is_some_and(|value| !value.is_empty());
+    if has("WAYLAND_DISPLAY") {
+        // X11 grabs only see keys pressed in XWayland windows.
+        vec![Backend::Portal]
+    } else if has("DISPLAY") {
+        vec![Backend::X11, Backend::Portal]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Toggles the window when the hotkey is pressed.
+/// Requires the window plugin.
+pub struct HotkeyPlugin;
+
+impl Plugin for HotkeyPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = app.world.resource::<settings::Settings>();
+        let hotkey = Hotkey::new(&settings.hotkey);
+        app.insert_resource(hotkey).add_system(hotkey_system);
+    }
+}
+
+fn hotkey_system(mut hotkey: ResMut<Hotkey>, mut window_commands: EventWriter<window::WindowCommand>) {
+    let messages: Vec<_> = hotkey.receiver.get_mut().unwrap().try_iter().collect();
+    for message in messages {
+        match message {
+            Message::Active(backend) => {
+                info!("Global hotkey {} bound via {backend:?}", hotkey.shortcut.as_ref().unwrap());
+                hotkey.status = HotkeyStatus::Active(backend);
+            }
+            Message::Unavailable(backend, e) => {
+                warn!("Global hotkey via {backend:?} is unavailable: {e}");
+                hotkey.start_next();
+            }
+            Message::Pressed => window_commands.send(window::WindowCommand::Toggle),
+        }
+    }
+}
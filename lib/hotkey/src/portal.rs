@@ -0,0 +1,43 @@
+//! Binds the shortcut through the desktop portal's GlobalShortcuts interface,
+//! the only way to get global shortcuts on Wayland.
+//! The desktop may ask the user to confirm or change the shortcut.
+
+use crate::{Backend, Message, Shortcut};
+use ashpd::desktop::global_shortcuts::{GlobalShortcuts, NewShortcut};
+use ashpd::WindowIdentifier;
+use futures_lite::StreamExt;
+use std::sync::mpsc::Sender;
+
+const SHORTCUT_ID: &str = "toggle";
+
+pub(crate) fn listen(shortcut: Shortcut, sender: Sender<Message>) {
+    std::thread::spawn(move || {
+        if let Err(e) = futures_lite::future::block_on(run(&shortcut, &sender)) {
+            let _ = sender.send(Message::Unavailable(Backend::Portal, e.to_string()));
+        }
+    });
+}
+
+async fn run(shortcut: &Shortcut, sender: &Sender<Message>) -> ashpd::Result<()> {
+    let portal = GlobalShortcuts::new().await?;
+    let session = portal.create_session().await?;
+
+    // Subscribing first, so no activation is missed.
+    let activated = portal.receive_activated().await?;
+    futures_lite::pin!(activated);
+
+    let trigger = shortcut.trigger();
+    let new_shortcut = NewShortcut::new(SHORTCUT_ID, "Show or hide Kumo").preferred_trigger(trigger.as_str());
+    portal
+        .bind_shortcuts(&session, &[new_shortcut], &WindowIdentifier::default())
+        .await?
+        .response()?;
+    let _ = sender.send(Message::Active(Backend::Portal));
+
+    while let Some(activated) = activated.next().await {
+        if activated.shortcut_id() == SHORTCUT_ID && sender.send(Message::Pressed).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,175 @@
+//! Shortcuts as written in the settings, e.g. `Ctrl+Alt+K`.
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    /// The Windows or Command key, called Logo by some desktops.
+    pub super_: bool,
+}
+
+/// A key, as X11 keysym.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Key {
+    /// The keysym's name, e.g. `k` or `space`.
+    pub name: String,
+    pub keysym: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Shortcut {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl Shortcut {
+    /// Parses modifiers and a key, joined by `+`. Case doesn't matter.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts: Vec<_> = text.split('+').map(str::trim).collect();
+        let key = parts.pop().filter(|key| !key.is_empty()).ok_or("The shortcut has no key")?;
+
+        let mut modifiers = Modifiers::default();
+        for part in parts {
+            let modifier = match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => &mut modifiers.ctrl,
+                "alt" => &mut modifiers.alt,
+                "shift" => &mut modifiers.shift,
+                "super" | "logo" | "win" | "meta" => &mut modifiers.super_,
+                _ => return Err(format!("Unknown modifier `{part}`")),
+            };
+            *modifier = true;
+        }
+
+        Ok(Self { modifiers, key: key_of(key).ok_or(format!("Unknown key `{key}`"))? })
+    }
+
+    /// Returns the shortcut as the XDG shortcuts specification writes it, e.g. `CTRL+ALT+k`.
+    /// The desktop portal takes it as preferred trigger.
+    pub fn trigger(&self) -> String {
+        let modifiers = [
+            (self.modifiers.ctrl, "CTRL"),
+            (self.modifiers.alt, "ALT"),
+            (self.modifiers.shift, "SHIFT"),
+            (self.modifiers.super_, "LOGO"),
+        ];
+        modifiers
+            .into_iter()
+            .filter(|(pressed, _)| *pressed)
+            .map(|(_, name)| name)
+            .chain([self.key.name.as_str()])
+            .collect::<Vec<_>>()
+            .join("+")
+    }
+}
+
+impl std::fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let modifiers = [
+            (self.modifiers.ctrl, "Ctrl"),
+            (self.modifiers.alt, "Alt"),
+            (self.modifiers.shift, "Shift"),
+            (self.modifiers.super_, "Super"),
+        ];
+        for (_, name) in modifiers.into_iter().filter(|(pressed, _)| *pressed) {
+            write!(f, "{name}+")?;
+        }
+        match self.key.name.as_str() {
+            name if name.len() == 1 => write!(f, "{}", name.to_ascii_uppercase()),
+            name => write!(f, "{}{}", name[..1].to_ascii_uppercase(), &name[1..]),
+        }
+    }
+}
+
+/// Returns the key of the name. Only keys that are on every layout are supported.
+fn key_of(name: &str) -> Option<Key> {
+    let key = |name: &str, keysym: u32| Some(Key { name: name.to_owned(), keysym });
+    let lowercase = name.to_ascii_lowercase();
+
+    match lowercase.as_str() {
+        // Latin letters and digits are their own keysyms.
+        single if single.len() == 1 && single.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            key(single, single.as_bytes()[0] as u32)
+        }
+        "space" => key("space", 0x0020),
+        "grave" | "`" => key("grave", 0x0060),
+        "tab" => key("Tab", 0xff09),
+        "return" | "enter" => key("Return", 0xff0d),
+        "escape" | "esc" => key("Escape", 0xff1b),
+        "pause" => key("Pause", 0xff13),
+        "home" => key("Home", 0xff50),
+        "end" => key("End", 0xff57),
+        "insert" => key("Insert", 0xff63),
+        "delete" => key("Delete", 0xffff),
+        function => {
+            let number: u32 = function.strip_prefix('f')?.parse().ok()?;
+            if (1..=24).contains(&number) {
+                key(&format!("F{number}"), 0xffbe + number - 1)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Shortcut {
+        Shortcut::parse(text).unwrap()
+    }
+
+    #[test]
+    fn modifiers() {
+        let shortcut = parse("Ctrl+Alt+K");
+        assert_eq!(shortcut.modifiers, Modifiers { ctrl: true, alt: true, ..Default::default() });
+        assert_eq!(shortcut.key, Key { name: "k".to_owned(), keysym: 0x006b });
+
+        let shortcut = parse(" control + SHIFT + logo + space ");
+        assert_eq!(shortcut.modifiers, Modifiers { ctrl: true, shift: true, super_: true, ..Default::default() });
+        assert_eq!(shortcut.key.name, "space");
+
+        assert_eq!(parse("F12").modifiers, Modifiers::default());
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(Shortcut::parse(""), Err("The shortcut has no key".to_owned()));
+        assert_eq!(Shortcut::parse("Ctrl+"), Err("The shortcut has no key".to_owned()));
+        assert_eq!(Shortcut::parse("Hyper+K"), Err("Unknown modifier `Hyper`".to_owned()));
+        assert_eq!(Shortcut::parse("Ctrl+Banana"), Err("Unknown key `Banana`".to_owned()));
+    }
+
+    #[test]
+    fn keys() {
+        let keysym = |name| key_of(name).map(|key| key.keysym);
+        assert_eq!(keysym("a"), Some(0x0061));
+        assert_eq!(keysym("A"), Some(0x0061));
+        assert_eq!(keysym("7"), Some(0x0037));
+        assert_eq!(keysym("`"), Some(0x0060));
+        assert_eq!(keysym("Enter"), Some(0xff0d));
+        assert_eq!(keysym("esc"), Some(0xff1b));
+        assert_eq!(keysym("F1"), Some(0xffbe));
+        assert_eq!(keysym("f24"), Some(0xffd5));
+
+        assert_eq!(keysym("F0"), None);
+        assert_eq!(keysym("F25"), None);
+        assert_eq!(keysym("ä"), None);
+        assert_eq!(keysym("-"), None);
+        assert_eq!(keysym("ab"), None);
+    }
+
+    #[test]
+    fn trigger() {
+        assert_eq!(parse("Ctrl+Alt+K").trigger(), "CTRL+ALT+k");
+        assert_eq!(parse("Super+Shift+Space").trigger(), "SHIFT+LOGO+space");
+        assert_eq!(parse("f5").trigger(), "F5");
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(parse("alt+ctrl+k").to_string(), "Ctrl+Alt+K");
+        assert_eq!(parse("Super+space").to_string(), "Super+Space");
+    }
+}
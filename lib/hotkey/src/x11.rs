@@ -0,0 +1,128 @@
+//! Grabs the shortcut on the X11 root window, so the X server sends us its key presses
+//! no matter which window is focused.
+
+use crate::{Backend, Message, Shortcut};
+use std::sync::mpsc::Sender;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt, GrabMode, ModMask, Timestamp};
+use x11rb::protocol::Event;
+
+pub(crate) fn listen(shortcut: Shortcut, sender: Sender<Message>) {
+    std::thread::spawn(move || {
+        if let Err(e) = run(&shortcut, &sender) {
+            let _ = sender.send(Message::Unavailable(Backend::X11, e));
+        }
+    });
+}
+
+fn run(shortcut: &Shortcut, sender: &Sender<Message>) -> Result<(), String> {
+    let (conn, screen) = x11rb::connect(None).map_err(|e| e.to_string())?;
+    let root = conn.setup().roots[screen].root;
+    let keycode = keycode(&conn, shortcut.key.keysym)?
+        .ok_or(format!("The keyboard has no `{}` key", shortcut.key.name))?;
+
+    let mut modifiers = ModMask::default();
+    for (pressed, modifier) in [
+        (shortcut.modifiers.ctrl, ModMask::CONTROL),
+        (shortcut.modifiers.alt, ModMask::M1),
+        (shortcut.modifiers.shift, ModMask::SHIFT),
+        (shortcut.modifiers.super_, ModMask::M4),
+    ] {
+        if pressed {
+            modifiers = modifiers | modifier;
+        }
+    }
+
+    // Grabs only match the exact modifiers, so Caps Lock and Num Lock would break them.
+    let mut grabbed = Vec::new();
+    for lock in [ModMask::default(), ModMask::LOCK, ModMask::M2, ModMask::LOCK | ModMask::M2] {
+        let grab = conn
+            .grab_key(false, root, modifiers | lock, keycode, GrabMode::ASYNC, GrabMode::ASYNC)
+            .map_err(|e| e.to_string())
+            .and_then(|cookie| {
+                cookie.check().map_err(|_| format!("{shortcut} is already taken by another program"))
+            });
+        if let Err(e) = grab {
+            // Half a shortcut would only get in the way of whoever has the rest.
+            for mask in grabbed {
+                let _ = conn.ungrab_key(keycode, root, mask);
+            }
+            let _ = conn.flush();
+            return Err(e);
+        }
+        grabbed.push(modifiers | lock);
+    }
+    let _ = sender.send(Message::Active(Backend::X11));
+
+    let mut repeats = Repeats::default();
+    loop {
+        let pressed = match conn.wait_for_event().map_err(|e| e.to_string())? {
+            Event::KeyPress(event) => !repeats.is_repeat(event.time),
+            Event::KeyRelease(event) => {
+                repeats.released(event.time);
+                false
+            }
+            _ => false,
+        };
+        if pressed && sender.send(Message::Pressed).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Tells the key presses of holding the key apart from actual presses.
+/// While a key is held, X repeats it as a release and a press at the same time.
+#[derive(Default)]
+struct Repeats {
+    released_at: Option<Timestamp>,
+}
+
+impl Repeats {
+    fn released(&mut self, time: Timestamp) {
+        self.released_at = Some(time);
+    }
+
+    fn is_repeat(&mut self, time: Timestamp) -> bool {
+        self.released_at.take() == Some(time)
+    }
+}
+
+/// Returns the first keycode that produces the keysym on the current layout.
+fn keycode(conn: &impl Connection, keysym: u32) -> Result<Option<u8>, String> {
+    let setup = conn.setup();
+    let (min, max) = (setup.min_keycode, setup.max_keycode);
+    let mapping = conn
+        .get_keyboard_mapping(min, max - min + 1)
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map_err(|e| e.to_string())?;
+
+    let per_keycode = mapping.keysyms_per_keycode.max(1) as usize;
+    Ok(mapping
+        .keysyms
+        .chunks(per_keycode)
+        .position(|keysyms| keysyms.contains(&keysym))
+        .map(|index| min + index as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats() {
+        let mut repeats = Repeats::default();
+        assert!(!repeats.is_repeat(100));
+
+        // Held down.
+        repeats.released(600);
+        assert!(repeats.is_repeat(600));
+        repeats.released(633);
+        assert!(repeats.is_repeat(633));
+
+        // Let go, and pressed again.
+        repeats.released(650);
+        assert!(!repeats.is_repeat(900));
+        assert!(!repeats.is_repeat(1200));
+    }
+}
@@ -1,41 +1,155 @@
 use bevy::prelude::*;
 
+mod link_view;
+mod settings_window;
+
 pub struct InterfacePlugin;
 
-#[derive(States, Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(States, Clone, Debug, Default, Hash, PartialEq, Eq)]
 pub enum InterfaceState {
+    #[default]
     Displaying
 }
 
-impl Default for InterfaceState {
-    fn default() -> Self {
-        Self::Displaying
-    }
-}
-
 impl Plugin for InterfacePlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(bevy_egui::EguiPlugin);
         app.add_state::<InterfaceState>();
 
+        app.init_resource::<settings_window::SettingsWindow>();
+        app.init_resource::<Browse>();
+        app.init_resource::<link_view::LinkView>();
+        app.init_resource::<Memory>();
+
         app.add_system(setup.in_schedule(OnEnter(InterfaceState::Displaying)));
         app.add_system(cleanup.in_schedule(OnExit(InterfaceState::Displaying)));
+        app.add_system(settings_window::failure_system);
+        app.add_system(settings_window::clear_failure_system
+            .in_schedule(OnEnter(deviantart::AuthState::Authenticated)));
+        app.add_system(request_system);
+        app.add_system(link_view::load_system.after(request_system));
         app.add_system(draw.in_set(OnUpdate(InterfaceState::Displaying)));
     }
 }
 
+/// What the user, or another process, asked the interface to show.
+#[derive(Resource, Default)]
+struct Browse {
+    query: String,
+}
+
+/// Applies requests forwarded from other processes.
+fn request_system(
+    mut browse: ResMut<Browse>,
+    mut link_view: ResMut<link_view::LinkView>,
+    mut requests: EventReader<ipc::Request>,
+) {
+    for request in requests.iter() {
+        match request {
+            ipc::Request::Search { query } => browse.query = query.clone(),
+            ipc::Request::Open { url } => match deviantart::Link::parse(url) {
+                Ok(link) => link_view.open(link),
+                Err(e) => warn!("Cannot open {url}: {e}"),
+            },
+            _ => {}
+        }
+    }
+}
+
+/// The egui state of the window, e.g. where its windows are and what was typed,
+/// kept outside the window, so it survives the window being created anew.
+#[derive(Resource, Default)]
+struct Memory {
+    /// The window it was last saved from.
+    window: Option<Entity>,
+    memory: egui::Memory,
+}
+
 fn setup() {}
 
 fn cleanup() {}
 
-fn draw(mut ctx: bevy_egui::EguiContexts, window: Res<window::WindowEntity>) {
+#[allow(clippy::too_many_arguments)]
+fn draw(
+    mut ctx: bevy_egui::EguiContexts,
+    window: Res<window::WindowEntity>,
+    mut memory: ResMut<Memory>,
+    mut settings_window: ResMut<settings_window::SettingsWindow>,
+    mut browse: ResMut<Browse>,
+    mut link_view: ResMut<link_view::LinkView>,
+    mut settings: ResMut<settings::Settings>,
+    auth_state: Res<State<deviantart::AuthState>>,
+    token: Option<Res<deviantart::TokenResponse>>,
+    monitors: Res<window::Monitors>,
+    theme: Res<theme::CurrentTheme>,
+    themes: Res<theme::Themes>,
+    meili_status: Res<meiliguard::MeilisearchStatus>,
+    mut authenticate: EventWriter<deviantart::Authenticate>,
+    mut logout: EventWriter<deviantart::Logout>,
+    mut window_commands: EventWriter<window::WindowCommand>,
+) {
     let Some(ctx) = ctx.try_ctx_for_window_mut(window.id) else {
         return;
     };
 
+    if memory.window != Some(window.id) {
+        if memory.window.is_some() {
+            ctx.memory_mut(|current| *current = memory.memory.clone());
+        }
+        // Textures belong to the context they were loaded into.
+        link_view.forget_textures();
+    }
+
+    // Also applies the theme to windows that were recreated.
+    let visuals = theme.0.visuals();
+    if ctx.style().visuals != visuals {
+        ctx.set_visuals(visuals);
+    }
+
     egui::Area::new("taskbar").anchor(egui::Align2::CENTER_BOTTOM, egui::Vec2::default()).show(ctx, |ui| {
-        egui::Frame::none().fill(egui::Color32::WHITE).show(ui, |ui| {
-            ui.label("Hello World~");
+        egui::Frame::none().fill(ui.visuals().panel_fill).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Hello World~");
+                ui.add(egui::TextEdit::singleline(&mut browse.query).hint_text("Search"));
+                if ui.button("Settings").clicked() {
+                    settings_window.toggle(&settings);
+                }
+                meili_status_label(ui, &meili_status);
+            });
         });
     });
+
+    link_view::draw(ctx, &mut link_view, token.is_some());
+
+    settings_window::draw(
+        ctx,
+        &mut settings_window,
+        &mut settings,
+        &monitors,
+        &theme.0,
+        &themes,
+        auth_state.0,
+        token.as_deref(),
+        &mut authenticate,
+        &mut logout,
+        &mut window_commands,
+    );
+
+    // Saved every frame, since the window may be gone by the next one,
+    // e.g. when it's hidden on Wayland.
+    memory.window = Some(window.id);
+    ctx.memory(|current| memory.memory.clone_from(current));
+}
+
+/// Tells why search doesn't work yet, or anymore.
+fn meili_status_label(ui: &mut egui::Ui, status: &meiliguard::MeilisearchStatus) {
+    match status {
+        meiliguard::MeilisearchStatus::Starting => {
+            ui.spinner().on_hover_text("Meilisearch is starting");
+        }
+        meiliguard::MeilisearchStatus::Ready => (),
+        meiliguard::MeilisearchStatus::Crashed(e) | meiliguard::MeilisearchStatus::Unavailable(e) => {
+            ui.colored_label(ui.visuals().error_fg_color, "Search is unavailable").on_hover_text(e);
+        }
+    }
 }
@@ -0,0 +1,248 @@
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use deviantart::{api, Link};
+
+/// A folder of a gallery or collection, as listed in the view.
+struct FolderEntry {
+    name: String,
+    size: Option<u32>,
+}
+
+/// A deviation, as shown in the view.
+struct DeviationEntry {
+    deviation: api::Deviation,
+    /// Tags aren't essential, so failing to load them isn't an error.
+    tags: Vec<String>,
+    /// Decoded on the task, and uploaded once it's drawn.
+    /// Kept to upload it again into a new window.
+    preview: Option<egui::ColorImage>,
+    texture: Option<egui::TextureHandle>,
+}
+
+/// What the view shows, once it's loaded.
+enum Content {
+    /// Galleries and collections list their folders.
+    Folders(Vec<FolderEntry>),
+    Deviation(Box<DeviationEntry>),
+}
+
+/// The view of a deviantart.com link opened from outside Kumo.
+#[derive(Resource, Default)]
+pub(crate) struct LinkView {
+    link: Option<Link>,
+    /// Loads the content, once authenticated.
+    task: Option<Task<Result<Content, String>>>,
+    content: Option<Result<Content, String>>,
+}
+
+impl LinkView {
+    pub(crate) fn open(&mut self, link: Link) {
+        *self = Self { link: Some(link), ..Default::default() };
+    }
+
+    /// Uploads the preview again the next time it's drawn, e.g. into a new window.
+    pub(crate) fn forget_textures(&mut self) {
+        if let Some(Ok(Content::Deviation(entry))) = &mut self.content {
+            entry.texture = None;
+        }
+    }
+}
+
+/// Loads the content of the opened link, and collects it once loaded.
+pub(crate) fn load_system(
+    mut view: ResMut<LinkView>,
+    token: Option<Res<deviantart::TokenResponse>>,
+    endpoints: Res<deviantart::Endpoints>,
+) {
+    if let Some(task) = &mut view.task {
+        if let Some(result) = futures_lite::future::block_on(futures_lite::future::poll_once(task)) {
+            view.task = None;
+            view.content = Some(result);
+        }
+        return;
+    }
+
+    let (Some(link), Some(token), None) = (view.link.clone(), token, &view.content) else {
+        return;
+    };
+
+    let client = token.api_client(&endpoints);
+    let task = AsyncComputeTaskPool::get().spawn(async move { load(&client, &link) });
+    view.task = Some(task);
+}
+
+/// Loads everything the view shows. This blocks, see `deviantart::api`.
+fn load(client: &api::Client, link: &Link) -> Result<Content, String> {
+    match link {
+        Link::Gallery { username, .. } | Link::User { username } => client
+            .gallery_folders(Some(username))
+            .map(|folder| folder.map(|f| FolderEntry { name: f.name, size: f.size }))
+            .collect::<api::Result<_>>()
+            .map(Content::Folders)
+            .map_err(|e| e.to_string()),
+        Link::Collection { username, .. } => client
+            .collection_folders(Some(username))
+            .map(|folder| folder.map(|f| FolderEntry { name: f.name, size: f.size }))
+            .collect::<api::Result<_>>()
+            .map(Content::Folders)
+            .map_err(|e| e.to_string()),
+        Link::Deviation { username, id, .. } => {
+            let deviation = client
+                .find_deviation(username, *id)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("It isn't among the first deviations in {username}'s gallery."))?;
+
+            let tags = match client.deviation_metadata(&[&deviation.deviationid]) {
+                Ok(metadata) => metadata
+                    .into_iter()
+                    .flat_map(|metadata| metadata.tags)
+                    .map(|tag| tag.tag_name)
+                    .collect(),
+                Err(e) => {
+                    warn!("Failed to load the tags of {}: {e}", link.url());
+                    Vec::new()
+                }
+            };
+
+            let preview = deviation.preview.as_ref().or(deviation.thumbs.last()).and_then(|image| {
+                match client.download(&image.src).map_err(|e| e.to_string()).and_then(|bytes| decode(&bytes)) {
+                    Ok(preview) => Some(preview),
+                    Err(e) => {
+                        warn!("Failed to load the preview of {}: {e}", link.url());
+                        None
+                    }
+                }
+            });
+
+            Ok(Content::Deviation(Box::new(DeviationEntry { deviation, tags, preview, texture: None })))
+        }
+    }
+}
+
+fn decode(bytes: &[u8]) -> Result<egui::ColorImage, String> {
+    let image = image::load_from_memory(bytes).map_err(|e| e.to_string())?.to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    Ok(egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw()))
+}
+
+pub(crate) fn draw(ctx: &egui::Context, view: &mut LinkView, authenticated: bool) {
+    let Some(link) = view.link.clone() else {
+        return;
+    };
+
+    let title = match &link {
+        Link::Deviation { title, .. } => title.clone(),
+        Link::Gallery { username, .. } => format!("Gallery of {username}"),
+        Link::Collection { username, .. } => format!("Favourites of {username}"),
+        Link::User { username } => username.clone(),
+    };
+
+    let mut open = true;
+    egui::Window::new(title).id(egui::Id::new("link_view")).open(&mut open).show(ctx, |ui| {
+        match &link {
+            Link::Deviation { .. } => (),
+            Link::Gallery { username, .. } | Link::User { username } => {
+                if ui.button("Favourites").clicked() {
+                    let username = username.clone();
+                    view.open(Link::Collection { username, folder: None });
+                }
+            }
+            Link::Collection { username, .. } => {
+                if ui.button("Gallery").clicked() {
+                    let username = username.clone();
+                    view.open(Link::Gallery { username, folder: None });
+                }
+            }
+        }
+
+        match &mut view.content {
+            Some(Ok(Content::Folders(folders))) => {
+                ui.separator();
+                draw_folders(ui, folders, &link);
+            }
+            Some(Ok(Content::Deviation(entry))) => draw_deviation(ui, entry),
+            Some(Err(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, format!("Failed to load: {e}"));
+            }
+            None if authenticated => {
+                ui.spinner();
+            }
+            None => {
+                ui.label("Authenticate with DeviantArt in the settings to see more.");
+            }
+        }
+
+        ui.separator();
+        ui.hyperlink_to("Open on DeviantArt", link.url());
+    });
+
+    if !open {
+        *view = LinkView::default();
+    }
+}
+
+fn draw_folders(ui: &mut egui::Ui, folders: &[FolderEntry], link: &Link) {
+    // The folder named in the URL is highlighted.
+    let selected = match link {
+        Link::Gallery { folder: Some(folder), .. } | Link::Collection { folder: Some(folder), .. } => {
+            Some(folder.name.as_str())
+        }
+        _ => None,
+    };
+
+    egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+        for folder in folders {
+            let is_selected = selected.is_some_and(|name| slug(&folder.name) == name.to_lowercase());
+            let text = match folder.size {
+                Some(size) => format!("{} ({size})", folder.name),
+                None => folder.name.clone(),
+            };
+            ui.add(egui::SelectableLabel::new(is_selected, text));
+        }
+    });
+}
+
+/// The preview is scaled down to at most this size.
+const PREVIEW_SIZE: egui::Vec2 = egui::vec2(480.0, 480.0);
+
+fn draw_deviation(ui: &mut egui::Ui, entry: &mut DeviationEntry) {
+    if let (Some(preview), None) = (&entry.preview, &entry.texture) {
+        let name = format!("preview-{}", entry.deviation.deviationid);
+        entry.texture = Some(ui.ctx().load_texture(name, preview.clone(), Default::default()));
+    }
+    if let Some(texture) = &entry.texture {
+        let size = texture.size_vec2();
+        let scale = (PREVIEW_SIZE.x / size.x).min(PREVIEW_SIZE.y / size.y).min(1.0);
+        ui.image(texture, size * scale);
+    }
+
+    let deviation = &entry.deviation;
+    if let Some(title) = &deviation.title {
+        ui.heading(title);
+    }
+    if let Some(author) = &deviation.author {
+        ui.label(format!("By {}", author.username));
+    }
+    if let Some(stats) = &deviation.stats {
+        ui.label(format!("{} favourites, {} comments", stats.favourites, stats.comments));
+    }
+    if deviation.is_mature {
+        ui.colored_label(ui.visuals().warn_fg_color, "Mature content");
+    }
+    if !entry.tags.is_empty() {
+        ui.horizontal_wrapped(|ui| {
+            for tag in &entry.tags {
+                ui.label(format!("#{tag}"));
+            }
+        });
+    }
+}
+
+/// Returns the folder name as it appears in URLs.
+fn slug(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
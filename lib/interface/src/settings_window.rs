@@ -0,0 +1,267 @@
+use bevy::prelude::*;
+
+/// The state of the settings window.
+/// The text fields are edited separately from the settings,
+/// so that incomplete input isn't applied on every keystroke.
+#[derive(Resource, Default)]
+pub(crate) struct SettingsWindow {
+    pub(crate) open: bool,
+    client_id: String,
+    client_secret: String,
+    ports: settings::Ports,
+    window_mode: settings::WindowMode,
+    monitor: Option<usize>,
+    /// The opacity being dragged to. It's applied once the slider is let go,
+    /// so the settings aren't saved on every frame.
+    opacity: Option<f32>,
+    /// The last reason authentication failed, if it failed.
+    last_failure: Option<String>,
+}
+
+impl SettingsWindow {
+    /// Opens the window, with the text fields reflecting the current settings.
+    pub(crate) fn toggle(&mut self, settings: &settings::Settings) {
+        self.open = !self.open;
+        if self.open {
+            self.client_id = settings.deviantart.client_id.clone();
+            self.client_secret = settings.deviantart.client_secret.clone();
+            self.ports = settings.ports;
+            self.window_mode = settings.window.mode;
+            self.monitor = settings.window.monitor;
+        }
+    }
+}
+
+pub(crate) fn failure_system(
+    mut window: ResMut<SettingsWindow>,
+    mut failures: EventReader<deviantart::AuthenticationFailed>,
+) {
+    if let Some(failure) = failures.iter().last() {
+        window.last_failure = Some(failure.0.clone());
+    }
+}
+
+/// Authentication succeeded, so the last failure is no longer relevant.
+pub(crate) fn clear_failure_system(mut window: ResMut<SettingsWindow>) {
+    window.last_failure = None;
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw(
+    ctx: &egui::Context,
+    window: &mut SettingsWindow,
+    settings: &mut ResMut<settings::Settings>,
+    monitors: &window::Monitors,
+    theme: &theme::Theme,
+    themes: &theme::Themes,
+    auth_state: deviantart::AuthState,
+    token: Option<&deviantart::TokenResponse>,
+    authenticate: &mut EventWriter<deviantart::Authenticate>,
+    logout: &mut EventWriter<deviantart::Logout>,
+    window_commands: &mut EventWriter<window::WindowCommand>,
+) {
+    let mut open = window.open;
+    // Only changes made here may replace a settings file that failed to load.
+    let unsaved = (settings.load_failed && open).then(|| settings::Settings::clone(settings));
+
+    egui::Window::new("Settings").open(&mut open).resizable(false).show(ctx, |ui| {
+        if settings.load_failed {
+            ui.colored_label(
+                ui.visuals().error_fg_color,
+                format!(
+                    "{} couldn't be read. Changing anything here replaces it.",
+                    settings::Settings::path().display()
+                ),
+            );
+            ui.separator();
+        }
+
+        ui.heading("DeviantArt");
+
+        egui::Grid::new("deviantart_credentials").num_columns(2).show(ui, |ui| {
+            ui.label("Client ID");
+            ui.text_edit_singleline(&mut window.client_id);
+            ui.end_row();
+
+            ui.label("Client Secret");
+            ui.add(egui::TextEdit::singleline(&mut window.client_secret).password(true));
+            ui.end_row();
+        });
+
+        let edited = settings::DeviantArtSettings {
+            client_id: window.client_id.trim().to_owned(),
+            client_secret: window.client_secret.trim().to_owned(),
+            ..settings.deviantart.clone()
+        };
+
+        ui.horizontal(|ui| {
+            let changed = edited != settings.deviantart;
+            if ui.add_enabled(changed, egui::Button::new("Save")).clicked() {
+                // Saving and applying happens through change detection.
+                settings.deviantart = edited.clone();
+            }
+            if let Err(e) = edited.credentials() {
+                ui.colored_label(ui.visuals().error_fg_color, e.to_string());
+            }
+        });
+
+        ui.separator();
+
+        if settings.deviantart.credentials().is_err() {
+            ui.label("Enter your application's credentials to use DeviantArt.");
+        } else {
+            auth_status(ui, window, auth_state, token, authenticate, logout);
+        }
+
+        ui.separator();
+        ui.heading("Window");
+
+        egui::Grid::new("window").num_columns(2).show(ui, |ui| {
+            ui.label("Mode");
+            egui::ComboBox::from_id_source("window_mode")
+                .selected_text(window.window_mode.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        settings::WindowMode::Windowed,
+                        settings::WindowMode::Borderless,
+                        settings::WindowMode::Fullscreen,
+                    ] {
+                        ui.selectable_value(&mut window.window_mode, mode, mode.to_string());
+                    }
+                });
+            ui.end_row();
+
+            let monitor_name = |monitor: Option<usize>| match monitor {
+                None => "Primary".to_owned(),
+                Some(index) => monitors.0.get(index)
+                    .cloned()
+                    .unwrap_or_else(|| format!("Monitor {} (unavailable)", index + 1)),
+            };
+            ui.label("Monitor");
+            egui::ComboBox::from_id_source("monitor")
+                .selected_text(monitor_name(window.monitor))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut window.monitor, None, monitor_name(None));
+                    for index in 0..monitors.0.len() {
+                        ui.selectable_value(&mut window.monitor, Some(index), monitor_name(Some(index)));
+                    }
+                });
+            ui.end_row();
+        });
+
+        let changed = window.window_mode != settings.window.mode || window.monitor != settings.window.monitor;
+        if ui.add_enabled(changed, egui::Button::new("Apply")).clicked() {
+            if window.monitor != settings.window.monitor {
+                // The remembered position is on the old monitor.
+                settings.window.position = None;
+            }
+            settings.window.mode = window.window_mode;
+            settings.window.monitor = window.monitor;
+            window_commands.send(window::WindowCommand::Recreate);
+        }
+
+        ui.separator();
+        ui.heading("Theme");
+
+        egui::Grid::new("theme").num_columns(2).show(ui, |ui| {
+            ui.label("Theme");
+            egui::ComboBox::from_id_source("theme")
+                .selected_text(settings.theme.name.clone())
+                .show_ui(ui, |ui| {
+                    for name in &themes.0 {
+                        if ui.selectable_label(&settings.theme.name == name, name).clicked() {
+                            settings.theme.name = name.clone();
+                        }
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Opacity");
+            ui.horizontal(|ui| {
+                let mut opacity = window.opacity.unwrap_or(theme.opacity);
+                let slider = ui.add(egui::Slider::new(&mut opacity, 0.0..=1.0));
+                if slider.drag_released() || (slider.changed() && !slider.dragged()) {
+                    settings.theme.opacity = Some(opacity);
+                    window.opacity = None;
+                } else if slider.changed() {
+                    window.opacity = Some(opacity);
+                }
+                if settings.theme.opacity.is_some() && ui.button("Use the theme's").clicked() {
+                    settings.theme.opacity = None;
+                }
+            });
+            ui.end_row();
+        });
+
+        ui.separator();
+        ui.heading("Ports");
+
+        egui::Grid::new("ports").num_columns(2).show(ui, |ui| {
+            ui.label("Instance");
+            ui.add(egui::DragValue::new(&mut window.ports.instance).clamp_range(1..=u16::MAX));
+            ui.end_row();
+
+            ui.label("OAuth2 Redirect");
+            ui.add(egui::DragValue::new(&mut window.ports.oauth).clamp_range(1..=u16::MAX));
+            ui.end_row();
+
+            ui.label("Meilisearch");
+            ui.add(egui::DragValue::new(&mut window.ports.meilisearch).clamp_range(1..=u16::MAX));
+            ui.end_row();
+        });
+
+        ui.horizontal(|ui| {
+            let changed = window.ports != settings.ports;
+            if ui.add_enabled(changed, egui::Button::new("Save")).clicked() {
+                settings.ports = window.ports;
+            }
+            ui.label("The instance and Meilisearch ports take effect after restarting Kumo.");
+        });
+    });
+
+    if unsaved.is_some_and(|unsaved| unsaved != **settings) {
+        settings.load_failed = false;
+    }
+
+    window.open = open;
+}
+
+fn auth_status(
+    ui: &mut egui::Ui,
+    window: &SettingsWindow,
+    auth_state: deviantart::AuthState,
+    token: Option<&deviantart::TokenResponse>,
+    authenticate: &mut EventWriter<deviantart::Authenticate>,
+    logout: &mut EventWriter<deviantart::Logout>,
+) {
+    match auth_state {
+        deviantart::AuthState::Unauthenticated | deviantart::AuthState::Failed => {
+            ui.label("Not authenticated.");
+            if ui.button("Authenticate").on_hover_text("Opens DeviantArt in the browser").clicked() {
+                authenticate.send(deviantart::Authenticate);
+            }
+        }
+        deviantart::AuthState::AwaitingBrowser => {
+            ui.label("Waiting for authorization in the browser...");
+            if ui.button("Cancel").clicked() {
+                logout.send(deviantart::Logout);
+            }
+        }
+        deviantart::AuthState::Exchanging => {
+            ui.label("Authenticating...");
+        }
+        deviantart::AuthState::Authenticated | deviantart::AuthState::Refreshing => {
+            ui.label("Authenticated.");
+            if let Some(token) = token {
+                ui.label(format!("Granted scopes: {}", token.scopes().join(", ")));
+            }
+            if ui.button("Log out").clicked() {
+                logout.send(deviantart::Logout);
+            }
+        }
+    }
+
+    if let (deviantart::AuthState::Failed, Some(failure)) = (auth_state, &window.last_failure) {
+        ui.colored_label(ui.visuals().error_fg_color, format!("Authentication failed: {failure}"));
+    }
+}
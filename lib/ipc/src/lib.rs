@@ -0,0 +1,259 @@
+//! The protocol Kumo instances, and scripts, use to talk to the running instance.
+//!
+//! Every message is a single line of JSON, which makes the protocol easy to use
+//! from scripts. The client sends one request per connection, and the running
+//! instance answers with one response:
+//!
+//! ```text
+//! > {"version":1,"secret":"...","request":{"command":"open","url":"https://www.deviantart.com/..."}}
+//! < {"version":1,"response":{"result":"ok"}}
+//! ```
+//!
+//! Requests must carry the running instance's [`Secret`];
+//! connections without it are dropped without a response.
+//! Instances only accept requests of their own protocol version,
+//! and answer others with an error response.
+//! See [`Endpoint`] for where the running instance listens.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Write};
+
+mod secret;
+mod transport;
+
+pub use secret::Secret;
+pub use transport::{Endpoint, Listener, Stream};
+
+/// The version of this protocol.
+/// Bump this whenever requests or responses change incompatibly.
+pub const VERSION: u32 = 1;
+
+/// Messages longer than this are rejected, so a misbehaving client can't exhaust our memory.
+const MAX_MESSAGE_LEN: u64 = 64 * 1024;
+
+/// A command for the running instance.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// Shows the window, creating it if necessary, and focuses it.
+    Show,
+    /// Hides the window.
+    Hide,
+    /// Shows the window if it's hidden, hides it otherwise.
+    Toggle,
+    /// Exits the running instance.
+    Quit,
+    /// Opens the given URL in the interface.
+    Open { url: String },
+    /// Searches for the given query in the interface.
+    Search { query: String },
+    /// Reports the status of the running instance.
+    Status,
+    /// Starts authenticating with DeviantArt.
+    Authenticate,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Response {
+    Ok,
+    Status(Status),
+    Error { message: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Status {
+    /// The version of the running Kumo.
+    pub version: String,
+    pub window_visible: bool,
+    /// The state of DeviantArt authentication.
+    pub deviantart: String,
+    /// Whether Meilisearch is ready.
+    pub meilisearch: String,
+}
+
+/// The request is kept as JSON value until we know we speak its version.
+#[derive(Serialize, Deserialize, Debug)]
+struct RequestEnvelope {
+    version: u32,
+    #[serde(default)]
+    secret: String,
+    request: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ResponseEnvelope {
+    version: u32,
+    response: Response,
+}
+
+/// Writes a single message as a line of JSON.
+fn write_line(writer: &mut impl Write, message: &impl Serialize) -> Result<()> {
+    let mut line = serde_json::to_vec(message).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    line.push(b'\n');
+    writer.write_all(&line)?;
+    writer.flush()
+}
+
+/// Reads a single line of JSON as message.
+fn read_line<T: DeserializeOwned>(reader: impl Read) -> Result<T> {
+    let mut reader = BufReader::new(reader.take(MAX_MESSAGE_LEN));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.ends_with('\n') {
+        return Err(Error::new(ErrorKind::InvalidData, "message too long or incomplete"));
+    }
+    serde_json::from_str(&line).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+pub fn write_request(writer: &mut impl Write, secret: &Secret, request: &Request) -> Result<()> {
+    let request = serde_json::to_value(request).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let secret = secret.as_str().to_owned();
+    write_line(writer, &RequestEnvelope { version: VERSION, secret, request })
+}
+
+/// Reads a request, if it carries the secret.
+/// The outer error is for transport errors and unauthenticated requests,
+/// after which we shouldn't respond.
+/// The inner error is for requests we can't handle, and should be sent as response.
+pub fn read_request(reader: impl Read, secret: &Secret) -> Result<std::result::Result<Request, String>> {
+    let envelope = read_line::<RequestEnvelope>(reader)?;
+    if !secret.matches(&envelope.secret) {
+        return Err(Error::new(ErrorKind::PermissionDenied, "missing or wrong secret"));
+    }
+    if envelope.version != VERSION {
+        return Ok(Err(format!(
+            "Unsupported protocol version {}, this instance speaks version {VERSION}",
+            envelope.version
+        )));
+    }
+    Ok(serde_json::from_value(envelope.request).map_err(|e| e.to_string()))
+}
+
+pub fn write_response(writer: &mut impl Write, response: &Response) -> Result<()> {
+    write_line(writer, &ResponseEnvelope { version: VERSION, response: response.clone() })
+}
+
+pub fn read_response(reader: impl Read) -> Result<Response> {
+    let envelope = read_line::<ResponseEnvelope>(reader)?;
+    Ok(envelope.response)
+}
+
+/// Sends the request over the stream, and waits for the response.
+pub fn send<S>(mut stream: S, secret: &Secret, request: &Request) -> Result<Response>
+where
+    S: Read + Write,
+{
+    write_request(&mut stream, secret, request)?;
+    read_response(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn request_line(secret: &Secret, request: &Request) -> Vec<u8> {
+        let mut line = Vec::new();
+        write_request(&mut line, secret, request).unwrap();
+        line
+    }
+
+    #[test]
+    fn round_trip() {
+        let secret = Secret::generate();
+        let request = Request::Open { url: "https://www.deviantart.com/someone".to_owned() };
+        let line = request_line(&secret, &request);
+        assert_eq!(read_request(Cursor::new(line), &secret).unwrap(), Ok(request));
+
+        let response = Response::Status(Status {
+            version: "0.1.0".to_owned(),
+            window_visible: true,
+            deviantart: "Authenticated".to_owned(),
+            meilisearch: "Ready".to_owned(),
+        });
+        let mut line = Vec::new();
+        write_response(&mut line, &response).unwrap();
+        assert_eq!(read_response(Cursor::new(line)).unwrap(), response);
+    }
+
+    #[test]
+    fn tags() {
+        let secret = Secret::generate();
+        let request = Request::Search { query: "clouds".to_owned() };
+        let line = String::from_utf8(request_line(&secret, &request)).unwrap();
+        let request = r#"{"command":"search","query":"clouds"}"#;
+        assert_eq!(
+            line,
+            format!("{{\"version\":{VERSION},\"secret\":\"{}\",\"request\":{request}}}\n", secret.as_str())
+        );
+
+        let mut line = Vec::new();
+        write_response(&mut line, &Response::Error { message: "no".to_owned() }).unwrap();
+        assert_eq!(
+            String::from_utf8(line).unwrap(),
+            format!("{{\"version\":{VERSION},\"response\":{{\"result\":\"error\",\"message\":\"no\"}}}}\n")
+        );
+
+        // As scripts would write them.
+        let line = format!(
+            "{{\"version\":1,\"secret\":\"{}\",\"request\":{{\"command\":\"quit\"}}}}\n",
+            secret.as_str()
+        );
+        assert_eq!(read_request(Cursor::new(line), &secret).unwrap(), Ok(Request::Quit));
+        let line = "{\"version\":1,\"response\":{\"result\":\"ok\"}}\n";
+        assert_eq!(read_response(Cursor::new(line)).unwrap(), Response::Ok);
+    }
+
+    #[test]
+    fn wrong_secret() {
+        let secret = Secret::generate();
+        let line = request_line(&Secret::generate(), &Request::Show);
+        let e = read_request(Cursor::new(line), &secret).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::PermissionDenied);
+
+        let line = "{\"version\":1,\"request\":{\"command\":\"show\"}}\n";
+        let e = read_request(Cursor::new(line), &secret).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn unsupported_requests() {
+        let secret = Secret::generate();
+        let line = format!(
+            "{{\"version\":{},\"secret\":\"{}\",\"request\":{{\"command\":\"show\"}}}}\n",
+            VERSION + 1,
+            secret.as_str()
+        );
+        let message = read_request(Cursor::new(line), &secret).unwrap().unwrap_err();
+        assert!(message.contains(&format!("version {}", VERSION + 1)), "{message}");
+
+        let line = format!(
+            "{{\"version\":{VERSION},\"secret\":\"{}\",\"request\":{{\"command\":\"dance\"}}}}\n",
+            secret.as_str()
+        );
+        assert!(read_request(Cursor::new(line), &secret).unwrap().is_err());
+    }
+
+    #[test]
+    fn too_long() {
+        let secret = Secret::generate();
+        let query = "a".repeat(MAX_MESSAGE_LEN as usize);
+        let line = request_line(&secret, &Request::Search { query });
+        let e = read_request(Cursor::new(line), &secret).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn incomplete() {
+        let secret = Secret::generate();
+        let mut line = request_line(&secret, &Request::Show);
+        line.pop();
+        let e = read_request(Cursor::new(line), &secret).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+
+        let e = read_response(Cursor::new("{\"version\":1,\"response\":{\"result\":\"ok\"}}")).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+    }
+}
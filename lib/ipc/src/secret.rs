@@ -0,0 +1,48 @@
+use std::io::Result;
+use std::path::PathBuf;
+
+/// Proves that a request comes from the user running Kumo.
+///
+/// The running instance generates a new secret at startup, and writes it to a file
+/// only the user can read. Every request must carry it, so other local users,
+/// who can't read the file, can't control Kumo.
+#[derive(Clone)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn path() -> PathBuf {
+        settings::config_dir().join("instance.secret")
+    }
+
+    /// Generates a new secret from the operating system's random number generator.
+    pub fn generate() -> Self {
+        Self(settings::random_hex_key())
+    }
+
+    /// Reads the secret of the running instance.
+    pub fn load() -> Result<Self> {
+        let text = std::fs::read_to_string(Self::path())?;
+        Ok(Self(text.trim().to_owned()))
+    }
+
+    /// Writes the secret for other processes to read.
+    pub fn save(&self) -> Result<()> {
+        settings::write_private(Self::path(), self.0.as_bytes())
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Compares in constant time, so the secret can't be guessed byte by byte.
+    pub(crate) fn matches(&self, other: &str) -> bool {
+        self.0.len() == other.len()
+            && self.0.bytes().zip(other.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
@@ -0,0 +1,215 @@
+//! The connections requests and responses travel over.
+//!
+//! On Unix, the running instance listens on a per-user socket in `$XDG_RUNTIME_DIR`,
+//! which other users can't connect to. Everywhere else, and if that isn't possible,
+//! it listens on the instance port on localhost instead.
+
+use std::io::{ErrorKind, Read, Result, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+
+/// The name of the socket in the runtime directory.
+#[cfg(unix)]
+const SOCKET_NAME: &str = "kumo.sock";
+
+/// An address the running instance may listen on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    #[cfg(unix)]
+    Unix(PathBuf),
+    Tcp(u16),
+}
+
+impl Endpoint {
+    /// Returns the endpoints to try, in order of preference.
+    pub fn candidates(port: u16) -> Vec<Self> {
+        let mut endpoints = Vec::new();
+        #[cfg(unix)]
+        if let Some(path) = socket_path() {
+            endpoints.push(Self::Unix(path));
+        }
+        endpoints.push(Self::Tcp(port));
+        endpoints
+    }
+
+    /// Whether only Kumo listens here.
+    /// The socket is in our user's runtime directory, which no other program uses for it,
+    /// whereas any program may listen on a port.
+    pub fn is_kumo_only(&self) -> bool {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(_) => true,
+            Self::Tcp(_) => false,
+        }
+    }
+
+    pub fn connect(&self) -> Result<Stream> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(path) => UnixStream::connect(path).map(Stream::Unix),
+            Self::Tcp(port) => TcpStream::connect(("127.0.0.1", *port)).map(Stream::Tcp),
+        }
+    }
+
+    /// Starts listening on this endpoint.
+    /// A socket left behind by an instance that didn't exit cleanly is replaced.
+    pub fn bind(&self) -> Result<Listener> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(path) => {
+                let listener = match UnixListener::bind(path) {
+                    Err(e) if e.kind() == ErrorKind::AddrInUse && remove_stale(path)? => {
+                        UnixListener::bind(path)?
+                    }
+                    result => result?,
+                };
+                Ok(Listener::Unix(listener, path.clone()))
+            }
+            Self::Tcp(port) => TcpListener::bind(("127.0.0.1", *port)).map(Listener::Tcp),
+        }
+    }
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(path) => write!(f, "{}", path.display()),
+            Self::Tcp(port) => write!(f, "127.0.0.1:{port}"),
+        }
+    }
+}
+
+/// Returns the path of the socket, if there is a runtime directory.
+#[cfg(unix)]
+fn socket_path() -> Option<PathBuf> {
+    let dir = PathBuf::from(std::env::var_os("XDG_RUNTIME_DIR")?);
+    // The specification says relative paths are invalid and should be ignored.
+    dir.is_absolute().then(|| dir.join(SOCKET_NAME))
+}
+
+/// Removes the socket at the path if no one listens on it anymore.
+/// Returns whether it was removed.
+#[cfg(unix)]
+fn remove_stale(path: &std::path::Path) -> Result<bool> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // We never remove something that isn't a socket.
+    if !std::fs::symlink_metadata(path)?.file_type().is_socket() {
+        return Ok(false);
+    }
+    match UnixStream::connect(path) {
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+            std::fs::remove_file(path)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+pub enum Listener {
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+    Tcp(TcpListener),
+}
+
+impl Listener {
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(listener, _) => listener.set_nonblocking(nonblocking),
+            Self::Tcp(listener) => listener.set_nonblocking(nonblocking),
+        }
+    }
+
+    /// Accepts a connection, and describes where it came from.
+    /// The stream is blocking, regardless of the listener.
+    pub fn accept(&self) -> Result<(Stream, String)> {
+        let (stream, peer) = match self {
+            #[cfg(unix)]
+            Self::Unix(listener, path) => {
+                let (stream, _addr) = listener.accept()?;
+                (Stream::Unix(stream), path.display().to_string())
+            }
+            Self::Tcp(listener) => {
+                let (stream, addr) = listener.accept()?;
+                (Stream::Tcp(stream), addr.to_string())
+            }
+        };
+        stream.set_nonblocking(false)?;
+        Ok((stream, peer))
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Self::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+pub enum Stream {
+    #[cfg(unix)]
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl Stream {
+    fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.set_nonblocking(nonblocking),
+            Self::Tcp(stream) => stream.set_nonblocking(nonblocking),
+        }
+    }
+
+    /// Sets the read and write timeout.
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(stream) => {
+                stream.set_read_timeout(timeout)?;
+                stream.set_write_timeout(timeout)
+            }
+            Self::Tcp(stream) => {
+                stream.set_read_timeout(timeout)?;
+                stream.set_write_timeout(timeout)
+            }
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.read(buf),
+            Self::Tcp(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.write(buf),
+            Self::Tcp(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.flush(),
+            Self::Tcp(stream) => stream.flush(),
+        }
+    }
+}
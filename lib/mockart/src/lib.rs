@@ -0,0 +1,337 @@
+//! A scriptable stand-in for DeviantArt's OAuth2 and API servers.
+//! It serves plain HTTP on a random port on localhost, so that tests can point
+//! `deviantart::Endpoints` at it and run without a network connection.
+//!
+//! The server answers `/oauth2/authorize` with a redirect to the given `redirect_uri`,
+//! `/oauth2/token` with a token, and anything under `/api/v1/oauth2` with whatever
+//! was scripted for that path. Every request is recorded for later inspection.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+type Headers = Vec<(String, String)>;
+
+const API_PREFIX: &str = "/api/v1/oauth2";
+
+const DEFAULT_TOKEN: &str = r#"{"status":"success","access_token":"mock-access-token","token_type":"Bearer","expires_in":3600,"refresh_token":"mock-refresh-token","scope":"browse"}"#;
+
+/// How the authorize endpoint responds.
+#[derive(Clone, Debug)]
+pub enum Authorize {
+    /// Redirects back with the given code.
+    Grant { code: String },
+    /// Redirects back with an error, as if the user denied access.
+    Deny { error: String, description: String },
+}
+
+impl Default for Authorize {
+    fn default() -> Self {
+        Self::Grant { code: "mock-code".to_owned() }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+/// A request the server received.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    /// Header names are lowercase.
+    pub headers: Headers,
+    pub body: String,
+}
+
+impl Request {
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Default)]
+struct Script {
+    authorize: Authorize,
+    token: Option<Response>,
+    api: HashMap<String, Response>,
+    requests: Vec<Request>,
+}
+
+/// The server runs until it is dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    script: Arc<Mutex<Script>>,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    pub fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+
+        let script = Arc::new(Mutex::new(Script::default()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let script = script.clone();
+            let shutdown = shutdown.clone();
+            std::thread::Builder::new()
+                .name("mockart".to_owned())
+                .spawn(move || {
+                    for stream in listener.incoming() {
+                        if shutdown.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        // A misbehaving client must not take the server down with it.
+                        #[allow(unused_must_use)] {
+                            stream.and_then(|stream| serve(stream, &script));
+                        }
+                    }
+                })?
+        };
+
+        Ok(Self { addr, script, shutdown, thread: Some(thread) })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn auth_url(&self) -> String {
+        format!("{}/oauth2/authorize", self.url())
+    }
+
+    pub fn token_url(&self) -> String {
+        format!("{}/oauth2/token", self.url())
+    }
+
+    pub fn api_url(&self) -> String {
+        format!("{}{}", self.url(), API_PREFIX)
+    }
+
+    pub fn set_authorize(&self, authorize: Authorize) {
+        self.script.lock().unwrap().authorize = authorize;
+    }
+
+    pub fn set_token_response(&self, status: u16, body: impl Into<String>) {
+        self.script.lock().unwrap().token = Some(Response { status, body: body.into() });
+    }
+
+    /// Scripts the response for an API path, relative to the API URL, e.g. `/user/whoami`.
+    /// Unscripted paths are answered with 404.
+    pub fn set_api_response(&self, path: &str, status: u16, body: impl Into<String>) {
+        self.script
+            .lock()
+            .unwrap()
+            .api
+            .insert(path.to_owned(), Response { status, body: body.into() });
+    }
+
+    /// Returns all requests received so far.
+    pub fn requests(&self) -> Vec<Request> {
+        self.script.lock().unwrap().requests.clone()
+    }
+
+    /// Acts as the browser: Opens the authorization URL and follows its redirect.
+    /// Returns the response of whoever listens at the redirect URI.
+    pub fn follow(&self, authorize_url: &str) -> Result<Response> {
+        let (_, headers, _) = get(authorize_url)?;
+        let location = headers
+            .into_iter()
+            .find(|(key, _)| key == "location")
+            .map(|(_, value)| value)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "no redirect"))?;
+        let (status, _, body) = get(&location)?;
+        Ok(Response { status, body })
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        // Wake the server thread, so it can notice the shutdown.
+        #[allow(unused_must_use)] {
+            TcpStream::connect(self.addr);
+        }
+        if let Some(thread) = self.thread.take() {
+            #[allow(unused_must_use)] {
+                thread.join();
+            }
+        }
+    }
+}
+
+fn serve(mut stream: TcpStream, script: &Mutex<Script>) -> Result<()> {
+    let request = read_request(&stream)?;
+
+    let (status, extra_headers, body) = {
+        let mut script = script.lock().unwrap();
+        script.requests.push(request.clone());
+        route(&request, &script)
+    };
+
+    let mut response = format!(
+        "HTTP/1.1 {} {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n",
+        status,
+        reason(status),
+        body.len()
+    );
+    for (key, value) in extra_headers {
+        response.push_str(&format!("{key}: {value}\r\n"));
+    }
+    response.push_str("\r\n");
+    response.push_str(&body);
+
+    stream.write_all(response.as_bytes())
+}
+
+fn route(request: &Request, script: &Script) -> (u16, Headers, String) {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/oauth2/authorize") => {
+            let Some(redirect_uri) = request.query("redirect_uri") else {
+                return (400, Vec::new(), error_body("invalid_request", "missing redirect_uri"));
+            };
+            let Ok(mut location) = url::Url::parse(redirect_uri) else {
+                return (400, Vec::new(), error_body("invalid_request", "invalid redirect_uri"));
+            };
+            {
+                let mut pairs = location.query_pairs_mut();
+                match &script.authorize {
+                    Authorize::Grant { code } => {
+                        pairs.append_pair("code", code);
+                    }
+                    Authorize::Deny { error, description } => {
+                        pairs.append_pair("error", error);
+                        pairs.append_pair("error_description", description);
+                    }
+                }
+                if let Some(state) = request.query("state") {
+                    pairs.append_pair("state", state);
+                }
+            }
+            (302, vec![("location".to_owned(), location.into())], String::new())
+        }
+        ("POST", "/oauth2/token") => match &script.token {
+            Some(response) => (response.status, Vec::new(), response.body.clone()),
+            None => (200, Vec::new(), DEFAULT_TOKEN.to_owned()),
+        },
+        (_, path) => match path.strip_prefix(API_PREFIX).and_then(|path| script.api.get(path)) {
+            Some(response) => (response.status, Vec::new(), response.body.clone()),
+            None => (404, Vec::new(), error_body("invalid_request", "not scripted")),
+        },
+    }
+}
+
+fn error_body(error: &str, description: &str) -> String {
+    format!(r#"{{"status":"error","error":"{error}","error_description":"{description}"}}"#)
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        302 => "Found",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        _ => "Unknown",
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(Error::new(ErrorKind::InvalidData, "malformed request line"));
+    };
+
+    let url = url::Url::parse(&format!("http://localhost{target}"))
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+    let headers = read_headers(&mut reader)?;
+
+    let content_length = headers
+        .iter()
+        .find(|(key, _)| key == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Request {
+        method: method.to_owned(),
+        path: url.path().to_owned(),
+        query: url.query_pairs().into_owned().collect(),
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+fn read_headers(reader: &mut impl BufRead) -> Result<Headers> {
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_lowercase(), value.trim().to_owned()));
+        }
+    }
+    Ok(headers)
+}
+
+/// A minimal HTTP client, so tests don't need one.
+fn get(url: &str) -> Result<(u16, Headers, String)> {
+    let url = url::Url::parse(url).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    let addr = url
+        .socket_addrs(|| Some(80))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "unresolvable host"))?;
+
+    let mut stream = TcpStream::connect(addr)?;
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_owned(),
+    };
+    write!(stream, "GET {target} HTTP/1.1\r\nhost: {}\r\nconnection: close\r\n\r\n", addr)?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "malformed status line"))?;
+
+    let headers = read_headers(&mut reader)?;
+
+    let mut body = String::new();
+    reader.read_to_string(&mut body)?;
+
+    Ok((status, headers, body))
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+/// The credentials of the application registered at https://www.deviantart.com/developers/.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct DeviantArtSettings {
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(skip_serializing_if = "DeviantArtEndpoints::is_empty")]
+    pub endpoints: DeviantArtEndpoints,
+}
+
+/// Replaces the URLs Kumo talks to, e.g. with those of a local mock server.
+/// Unset URLs are DeviantArt's. Kumo reads them at startup, and the command line overrides them.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct DeviantArtEndpoints {
+    pub auth_url: Option<String>,
+    pub token_url: Option<String>,
+    /// Defaults to localhost on the OAuth2 port. It must have a port to listen on.
+    pub redirect_url: Option<String>,
+    pub api_url: Option<String>,
+}
+
+impl DeviantArtEndpoints {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialsError {
+    /// Neither a client id nor a client secret was entered.
+    NotConfigured,
+    /// The client id is missing or not a number.
+    InvalidClientId,
+    /// The client secret is missing or not hexadecimal.
+    InvalidClientSecret,
+}
+
+impl std::fmt::Display for CredentialsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotConfigured => write!(f, "DeviantArt credentials are not configured"),
+            Self::InvalidClientId => write!(f, "The DeviantArt client id must be a number"),
+            Self::InvalidClientSecret => write!(f, "The DeviantArt client secret must be hexadecimal"),
+        }
+    }
+}
+
+impl std::error::Error for CredentialsError {}
+
+impl DeviantArtSettings {
+    /// Returns the trimmed client id and client secret, if they look valid.
+    pub fn credentials(&self) -> Result<(&str, &str), CredentialsError> {
+        let client_id = self.client_id.trim();
+        let client_secret = self.client_secret.trim();
+
+        if client_id.is_empty() && client_secret.is_empty() {
+            return Err(CredentialsError::NotConfigured);
+        }
+        if client_id.is_empty() || !client_id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(CredentialsError::InvalidClientId);
+        }
+        if client_secret.is_empty() || !client_secret.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(CredentialsError::InvalidClientSecret);
+        }
+
+        Ok((client_id, client_secret))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials(client_id: &str, client_secret: &str) -> Result<(String, String), CredentialsError> {
+        let settings = DeviantArtSettings {
+            client_id: client_id.to_owned(),
+            client_secret: client_secret.to_owned(),
+            ..Default::default()
+        };
+        settings.credentials().map(|(id, secret)| (id.to_owned(), secret.to_owned()))
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(credentials("", ""), Err(CredentialsError::NotConfigured));
+        assert_eq!(credentials("", "0123abcd"), Err(CredentialsError::InvalidClientId));
+        assert_eq!(credentials("12345", ""), Err(CredentialsError::InvalidClientSecret));
+    }
+
+    #[test]
+    fn whitespace_only() {
+        assert_eq!(credentials(" ", "\t\n"), Err(CredentialsError::NotConfigured));
+        assert_eq!(credentials("  ", "0123abcd"), Err(CredentialsError::InvalidClientId));
+        assert_eq!(credentials("12345", "   "), Err(CredentialsError::InvalidClientSecret));
+    }
+
+    #[test]
+    fn padded() {
+        assert_eq!(credentials(" 12345\n", "\t0123abcd "), Ok(("12345".to_owned(), "0123abcd".to_owned())));
+        // Only the ends are trimmed.
+        assert_eq!(credentials("123 45", "0123abcd"), Err(CredentialsError::InvalidClientId));
+        assert_eq!(credentials("12345", "0123 abcd"), Err(CredentialsError::InvalidClientSecret));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(credentials("client-id", "0123abcd"), Err(CredentialsError::InvalidClientId));
+        assert_eq!(credentials("12345", "secret"), Err(CredentialsError::InvalidClientSecret));
+    }
+}
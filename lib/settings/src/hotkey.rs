@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// The global hotkey that shows and hides the window.
+/// It's bound once when Kumo starts, so changes need a restart.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct HotkeySettings {
+    pub enabled: bool,
+    /// Modifiers and a key, joined by `+`, e.g. `Ctrl+Alt+K`.
+    /// The modifiers are `Ctrl`, `Alt`, `Shift` and `Super`.
+    pub shortcut: String,
+}
+
+impl Default for HotkeySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            shortcut: "Ctrl+Alt+K".to_owned(),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Kumo's settings, stored as TOML in the platform's config directory,
+//! e.g. `~/.config/kumo/settings.toml` on Linux.
+//! The settings are inserted as resource; changes to it are written back to disk.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind, Result, Write};
+use std::path::PathBuf;
+
+mod deviantart;
+mod hotkey;
+mod meilisearch;
+mod ports;
+mod theme;
+mod window;
+
+pub use deviantart::{CredentialsError, DeviantArtEndpoints, DeviantArtSettings};
+pub use hotkey::HotkeySettings;
+pub use meilisearch::MeilisearchSettings;
+pub use ports::Ports;
+pub use theme::ThemeSettings;
+pub use window::{WindowMode, WindowSettings};
+
+#[derive(Resource, Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub deviantart: DeviantArtSettings,
+    pub ports: Ports,
+    pub window: WindowSettings,
+    pub hotkey: HotkeySettings,
+    pub theme: ThemeSettings,
+    pub meilisearch: MeilisearchSettings,
+    /// Set if the settings file couldn't be read, so these are the defaults.
+    /// While it's set, changes aren't saved automatically, so the file can still be fixed.
+    /// The settings window clears it once the user changes something there.
+    #[serde(skip)]
+    pub load_failed: bool,
+}
+
+/// Returns the directory Kumo keeps its settings and other per-user state in.
+/// Falls back to the executable's directory if the platform has no config directory.
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .map(|config_dir| config_dir.join("kumo"))
+        .unwrap_or_else(|| {
+            let exe_path = std::env::current_exe().unwrap();
+            exe_path.parent().unwrap().to_owned()
+        })
+}
+
+/// Writes the file, creating its directory if necessary.
+/// On Unix, the file is only readable and writable by the current user.
+pub fn write_private(path: impl Into<PathBuf>, bytes: &[u8]) -> Result<()> {
+    let path = path.into();
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+
+        // The mode only applies to newly created files, so we also
+        // restrict files that were created with looser permissions.
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            let mut permissions = metadata.permissions();
+            permissions.set_mode(0o600);
+            std::fs::set_permissions(&path, permissions)?;
+        }
+    }
+
+    options.open(&path)?.write_all(bytes)
+}
+
+/// Generates a 256 bit key as hex, from the operating system's random number generator.
+/// Used for secrets kept in files written by [`write_private`].
+pub fn random_hex_key() -> String {
+    let bytes: [u8; 32] = rand::Rng::gen(&mut rand::rngs::OsRng);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        config_dir().join("settings.toml")
+    }
+
+    /// Loads the settings from disk.
+    /// If there is no settings file yet, the defaults are returned.
+    pub fn load() -> Result<Self> {
+        match std::fs::read_to_string(Self::path()) {
+            Ok(text) => toml::from_str(&text).map_err(|e| Error::new(ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes the settings to disk.
+    /// The file is private, since it contains API credentials.
+    pub fn save(&self) -> Result<()> {
+        let text = toml::to_string_pretty(self).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        write_private(Self::path(), text.as_bytes())
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = match Settings::load() {
+            Ok(settings) => settings,
+            Err(e) => {
+                // We don't overwrite a broken file with defaults until the user changes something.
+                error!(
+                    "Failed to load settings from {}: {e}. \
+                    They won't be saved until you change them in the settings window.",
+                    Settings::path().display()
+                );
+                Settings { load_failed: true, ..Default::default() }
+            }
+        };
+
+        app.insert_resource(settings.ports);
+        app.insert_resource(settings);
+        app.add_system(save_system);
+    }
+}
+
+fn save_system(settings: Res<Settings>) {
+    if !settings.is_changed() || settings.is_added() || settings.load_failed {
+        return;
+    }
+
+    match settings.save() {
+        Ok(()) => info!("Saved settings to {}", Settings::path().display()),
+        Err(e) => error!("Failed to save settings to {}: {e}", Settings::path().display()),
+    }
+}
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// These are read once, when Kumo starts Meilisearch, so changes need a restart.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct MeilisearchSettings {
+    /// How many seconds Meilisearch may take to shut down before it's killed.
+    /// Killing it while it writes can leave its database in an unclean state.
+    pub shutdown_grace_period: u64,
+}
+
+impl Default for MeilisearchSettings {
+    fn default() -> Self {
+        Self {
+            shutdown_grace_period: 10,
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The local ports Kumo uses.
+///
+/// As part of the settings, these are the configured ports.
+/// As resource, these are the ports in use, which may be overridden on the command line.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct Ports {
+    /// Used to achieve single instancing, and inter-process communication.
+    /// Kumo listens on it from startup on, so changes need a restart.
+    pub instance: u16,
+    /// Used for DeviantArt's OAuth2 redirect.
+    /// The redirect URI registered with DeviantArt must use this port.
+    /// Changes apply to the next authentication.
+    pub oauth: u16,
+    /// Used by the Meilisearch server, which is started with Kumo, so changes need a restart.
+    pub meilisearch: u16,
+}
+
+impl Default for Ports {
+    fn default() -> Self {
+        Self {
+            instance: 11210,
+            oauth: 11211,
+            meilisearch: 11212,
+        }
+    }
+}
+
+impl Ports {
+    /// Returns the configured ports.
+    /// This works without the settings resource, since the instance port is needed
+    /// before the app is built.
+    pub fn load() -> Self {
+        crate::Settings::load()
+            .map(|settings| settings.ports)
+            .unwrap_or_default()
+    }
+}
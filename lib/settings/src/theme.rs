@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct ThemeSettings {
+    /// `light`, `dark`, or the name of a custom theme in the assets' `themes` folder.
+    pub name: String,
+    /// Overrides the theme's background opacity, from 0 to 1.
+    pub opacity: Option<f32>,
+    /// Overrides whether the theme asks the compositor to blur behind the window.
+    pub blur: Option<bool>,
+}
+
+impl Default for ThemeSettings {
+    fn default() -> Self {
+        Self {
+            name: "dark".to_owned(),
+            opacity: None,
+            blur: None,
+        }
+    }
+}
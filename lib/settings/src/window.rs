@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMode {
+    /// A regular, resizable window with decorations.
+    Windowed,
+    /// Covers the monitor, without changing its video mode.
+    #[default]
+    Borderless,
+    /// Takes over the monitor exclusively.
+    Fullscreen,
+}
+
+impl std::fmt::Display for WindowMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Windowed => write!(f, "Windowed"),
+            Self::Borderless => write!(f, "Borderless"),
+            Self::Fullscreen => write!(f, "Fullscreen"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub mode: WindowMode,
+    /// The index of the monitor to open on. Defaults to the primary monitor.
+    pub monitor: Option<usize>,
+    /// Where the window was last, in physical pixels. Only used when windowed.
+    pub position: Option<[i32; 2]>,
+    /// How large the window was last, in logical pixels. Only used when windowed.
+    pub size: Option<[f32; 2]>,
+}
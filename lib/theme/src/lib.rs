@@ -0,0 +1,280 @@
+//! Kumo's look, shared by the window and the interface.
+//!
+//! Themes are TOML files with the `theme` extension in the assets' `themes` folder,
+//! and are reloaded whenever they change on disk. `light` and `dark` are built in,
+//! files of the same name replace them. New files are found after restarting Kumo.
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+
+/// A color, written as `#rrggbb` in theme files.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct Rgb(pub [u8; 3]);
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("`{text}` is not a color like `#1a2b3c`");
+        // Checking the digits first, as slicing by bytes would panic inside non-ASCII characters,
+        // and `from_str_radix` accepts signs.
+        let hex = text.strip_prefix('#')
+            .filter(|hex| hex.len() == 6 && hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .ok_or_else(invalid)?;
+        let channel = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid());
+        Ok(Self([channel(0)?, channel(1)?, channel(2)?]))
+    }
+}
+
+impl From<Rgb> for egui::Color32 {
+    fn from(Rgb([r, g, b]): Rgb) -> Self {
+        egui::Color32::from_rgb(r, g, b)
+    }
+}
+
+/// Missing fields are taken from the dark theme.
+#[derive(Deserialize, TypeUuid, Clone, Debug, PartialEq)]
+#[uuid = "6f1f0e52-3c1a-4b8e-9d8f-2f4b7f1e5a90"]
+#[serde(default)]
+pub struct Theme {
+    /// Whether the interface builds on egui's dark or light look.
+    pub dark: bool,
+    /// Fills the window behind the interface.
+    pub background: Rgb,
+    /// How opaque the background is, from 0 to 1.
+    /// The desktop shows through the rest, since the window is transparent.
+    pub opacity: f32,
+    /// Asks the compositor to blur what's behind the window.
+    /// This is only a hint, which few compositors honor.
+    pub blur: bool,
+    /// Fills the taskbar and other windows of the interface.
+    pub panel: Rgb,
+    pub text: Rgb,
+    /// Highlights selections and links.
+    pub accent: Rgb,
+    pub error: Rgb,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            dark: true,
+            background: Rgb([0x0d, 0x0d, 0x0d]),
+            opacity: 0.5,
+            blur: false,
+            panel: Rgb([0x1b, 0x1b, 0x1b]),
+            text: Rgb([0xdc, 0xdc, 0xdc]),
+            accent: Rgb([0x05, 0xcc, 0x47]),
+            error: Rgb([0xff, 0x80, 0x80]),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            dark: false,
+            background: Rgb([0xf2, 0xf2, 0xf2]),
+            opacity: 0.5,
+            blur: false,
+            panel: Rgb([0xff, 0xff, 0xff]),
+            text: Rgb([0x28, 0x28, 0x28]),
+            accent: Rgb([0x00, 0x8a, 0x2e]),
+            error: Rgb([0xc0, 0x20, 0x20]),
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+
+    /// Returns the interface's look.
+    pub fn visuals(&self) -> egui::Visuals {
+        let mut visuals = if self.dark { egui::Visuals::dark() } else { egui::Visuals::light() };
+        visuals.override_text_color = Some(self.text.into());
+        visuals.panel_fill = self.panel.into();
+        visuals.window_fill = self.panel.into();
+        visuals.hyperlink_color = self.accent.into();
+        visuals.selection.bg_fill = self.accent.into();
+        visuals.error_fg_color = self.error.into();
+        visuals
+    }
+
+    /// Returns the background in sRGB, with the opacity as alpha.
+    pub fn background_rgba(&self) -> [f32; 4] {
+        let [r, g, b] = self.background.0.map(|channel| channel as f32 / 255.0);
+        [r, g, b, self.opacity.clamp(0.0, 1.0)]
+    }
+
+    fn with_overrides(mut self, settings: &settings::ThemeSettings) -> Self {
+        self.opacity = settings.opacity.unwrap_or(self.opacity);
+        self.blur = settings.blur.unwrap_or(self.blur);
+        self
+    }
+}
+
+/// The theme in use, with the settings' overrides applied.
+/// It only changes when the theme does, so change detection can be used to apply it.
+#[derive(Resource, Default)]
+pub struct CurrentTheme(pub Theme);
+
+/// The names of the available themes, for the settings to choose from.
+#[derive(Resource, Default)]
+pub struct Themes(pub Vec<String>);
+
+/// The theme files found at startup. Holding the handles keeps them loaded,
+/// which keeps them reloading when they change.
+#[derive(Resource, Default)]
+struct ThemeFiles(Vec<Handle<Theme>>);
+
+#[derive(Default)]
+struct ThemeLoader;
+
+impl AssetLoader for ThemeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let theme: Theme = toml::from_str(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(theme));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["theme"]
+    }
+}
+
+/// Requires the asset plugin.
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Theme>();
+        app.init_asset_loader::<ThemeLoader>();
+
+        let files = match app.world.resource::<AssetServer>().load_folder("themes") {
+            Ok(handles) => handles.into_iter().map(HandleUntyped::typed).collect(),
+            Err(e) => {
+                warn!("No custom themes, since the themes folder can't be read: {e}");
+                Vec::new()
+            }
+        };
+        app.insert_resource(ThemeFiles(files));
+        app.init_resource::<Themes>();
+
+        let settings = &app.world.resource::<settings::Settings>().theme;
+        let theme = Theme::builtin(&settings.name).unwrap_or_default().with_overrides(settings);
+        app.insert_resource(CurrentTheme(theme));
+
+        app.add_system(select_system);
+    }
+}
+
+/// Finds the selected theme whenever the settings or theme files change.
+fn select_system(
+    settings: Res<settings::Settings>,
+    asset_server: Res<AssetServer>,
+    files: Res<ThemeFiles>,
+    assets: Res<Assets<Theme>>,
+    mut events: EventReader<AssetEvent<Theme>>,
+    mut themes: ResMut<Themes>,
+    mut current: ResMut<CurrentTheme>,
+) {
+    let files_changed = events.iter().count() > 0;
+    if !files_changed && !settings.is_changed() {
+        return;
+    }
+
+    let file_name = |handle: &Handle<Theme>| {
+        asset_server.get_handle_path(handle)
+            .and_then(|path| path.path().file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+    };
+
+    let mut names = vec!["dark".to_owned(), "light".to_owned()];
+    for handle in &files.0 {
+        if let (Some(name), Some(_)) = (file_name(handle), assets.get(handle)) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    themes.0 = names;
+
+    let name = &settings.theme.name;
+    let handle = files.0.iter().find(|handle| file_name(handle).as_ref() == Some(name));
+    let theme = match (handle.and_then(|handle| assets.get(handle)), Theme::builtin(name)) {
+        (Some(file), _) => file.clone(),
+        (None, Some(builtin)) => builtin,
+        // The file hasn't loaded yet, or failed to, which the asset server reports.
+        (None, None) if handle.is_some() => return,
+        (None, None) => {
+            if settings.is_changed() {
+                warn!("There is no theme named `{name}`");
+            }
+            return;
+        }
+    };
+
+    let theme = theme.with_overrides(&settings.theme);
+    if theme != current.0 {
+        current.0 = theme;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Rgb, String> {
+        Rgb::try_from(text.to_owned())
+    }
+
+    #[test]
+    fn valid_colors() {
+        assert_eq!(parse("#1a2b3c"), Ok(Rgb([0x1a, 0x2b, 0x3c])));
+        assert_eq!(parse("#FFFFFF"), Ok(Rgb([0xff, 0xff, 0xff])));
+    }
+
+    #[test]
+    fn wrong_length() {
+        assert!(parse("#fff").is_err());
+        assert!(parse("#1a2b3c4d").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn invalid_characters() {
+        assert!(parse("1a2b3c").is_err());
+        assert!(parse("#1a2b3g").is_err());
+        assert!(parse("#+1+2+3").is_err());
+    }
+
+    #[test]
+    fn non_ascii() {
+        // Six bytes, but `é` takes two of them.
+        assert!(parse("#a\u{e9}123").is_err());
+        assert!(parse("#\u{e9}\u{e9}\u{e9}").is_err());
+    }
+
+    #[test]
+    fn theme_file_with_invalid_color() {
+        let result = toml::from_str::<Theme>("background = \"#a\u{e9}123\"");
+        assert!(result.is_err());
+    }
+}
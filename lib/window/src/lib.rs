@@ -1,17 +1,42 @@
 use bevy::prelude::*;
 
+/// How long the window has to stay put before its geometry is saved,
+/// so dragging it around doesn't write the settings on every frame.
+const GEOMETRY_SAVE_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
 /// Returns the window configuration.
-pub fn window() -> Window {
-    Window {
+pub fn window(settings: &settings::WindowSettings) -> Window {
+    let windowed = settings.mode == settings::WindowMode::Windowed;
+
+    let mut window = Window {
         present_mode: bevy::window::PresentMode::Fifo,
-        mode: bevy::window::WindowMode::BorderlessFullscreen,
+        mode: match settings.mode {
+            settings::WindowMode::Windowed => bevy::window::WindowMode::Windowed,
+            settings::WindowMode::Borderless => bevy::window::WindowMode::BorderlessFullscreen,
+            settings::WindowMode::Fullscreen => bevy::window::WindowMode::Fullscreen,
+        },
         title: "Kumo".to_string(),
-        resizable: false,
-        decorations: false,
+        resizable: windowed,
+        decorations: windowed,
         focused: true,
         transparent: true,
         ..Default::default()
+    };
+
+    // Bevy only places windowed windows. Fullscreen windows are moved to
+    // their monitor once they exist, see `apply_system`.
+    window.position = match (settings.position, settings.monitor) {
+        (Some([x, y]), _) => bevy::window::WindowPosition::At(IVec2::new(x, y)),
+        (None, Some(monitor)) => bevy::window::WindowPosition::Centered(
+            bevy::window::MonitorSelection::Index(monitor),
+        ),
+        (None, None) => bevy::window::WindowPosition::Centered(bevy::window::MonitorSelection::Primary),
+    };
+    if let Some([width, height]) = settings.size {
+        window.resolution.set(width, height);
     }
+
+    window
 }
 
 pub const WINDOW_ICON: &[u8] = include_bytes!("../assets/kumo.png");
@@ -26,12 +51,51 @@ pub fn icon() -> winit::window::Icon {
         image.into_rgba8().into_raw(), width, height).unwrap()
 }
 
+/// Changes to the window, sent as event.
+/// This is the only way the window should be shown, hidden or created.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowCommand {
+    /// Shows and focuses the window, creating it if it doesn't exist.
+    Show,
+    /// Hides the window. It keeps existing, except on Wayland, where windows can't hide
+    /// and are destroyed instead. Shown again, they are created anew.
+    Hide,
+    /// Shows the window if it is hidden, and hides it otherwise.
+    Toggle,
+    /// Destroys the window and creates it anew, e.g. to apply settings
+    /// that only take effect on creation.
+    Recreate,
+}
+
 #[derive(Resource)]
 pub struct WindowEntity {
     pub id: Entity,
+    /// Whether the window is shown. Hidden windows still exist, except on Wayland.
+    pub visible: bool,
     /// This is an explicit flag to more thoroughly check whether this window is new.
     /// It replaces .is_changed on this resource.
-    pub needs_icon: bool,
+    needs_icon: bool,
+    /// Whether `visible` has yet to be applied to the winit window.
+    needs_sync: bool,
+    /// Whether the window has yet to be moved to its monitor.
+    needs_placement: bool,
+    /// Whether the theme's blur hint has yet to be applied.
+    needs_blur: bool,
+}
+
+/// The names of the available monitors, in the order `settings::WindowSettings::monitor` indexes.
+/// This is updated whenever the window is created.
+#[derive(Resource, Default)]
+pub struct Monitors(pub Vec<String>);
+
+impl WindowEntity {
+    fn new(id: Entity) -> Self {
+        Self { id, visible: true, needs_icon: true, needs_sync: false, needs_placement: true, needs_blur: true }
+    }
+
+    fn spawn(commands: &mut Commands, settings: &settings::WindowSettings) -> Self {
+        Self::new(commands.spawn(window(settings)).insert(bevy::window::PrimaryWindow).id())
+    }
 }
 
 pub struct WindowPlugin;
@@ -40,20 +104,28 @@ impl Plugin for WindowPlugin {
     fn build(&self, app: &mut App) {
         // Setup Bevy's Window plugin.
         // We do not exit automatically!
-        // The same instance can be brought up again
+        // Closing the window only hides it, and it can be brought up again
         // by simply starting Kumo again. See main.rs.
         app.add_plugin(bevy::window::WindowPlugin {
             primary_window: None,
             exit_condition: bevy::window::ExitCondition::DontExit,
-            close_when_requested: true,
+            close_when_requested: false,
         });
 
-        // We start with our window spawned.
-        let window = app.world.spawn(window())
+        // We start with our window spawned,
+        // and save our primary window entity id for later use.
+        let settings = app.world.resource::<settings::Settings>().window.clone();
+        let window = app.world.spawn(window(&settings))
             .insert(bevy::window::PrimaryWindow).id();
+        app.insert_resource(WindowEntity::new(window));
+        app.init_resource::<Monitors>();
 
-        // We save our primary window entity id for later use.
-        app.insert_resource(WindowEntity { id: window, needs_icon: true });
+        app.add_event::<WindowCommand>();
+        app.add_system(close_system.before(command_system));
+        app.add_system(command_system);
+        app.add_system(theme_system.before(apply_system));
+        app.add_system(apply_system.after(command_system));
+        app.add_system(geometry_system);
 
         // Before adding winit, we configure it to be lighter on resources.
         app.insert_resource(bevy::winit::WinitSettings {
@@ -77,7 +149,225 @@ impl Plugin for WindowPlugin {
         app.add_plugin(ImagePlugin::default_nearest());
         app.add_plugin(bevy::render::pipelined_rendering::PipelinedRenderingPlugin);
         app.add_plugin(bevy::core_pipeline::CorePipelinePlugin);
-        app.insert_resource(ClearColor(Color::rgba(0.05, 0.05, 0.05, 0.5)));
+        // The background is the theme's, see `theme_system`.
+        app.init_resource::<ClearColor>();
         app.world.spawn(Camera2dBundle::default());
     }
-}
\ No newline at end of file
+}
+
+/// Closing the window hides it instead.
+fn close_system(
+    window: Res<WindowEntity>,
+    mut closed: EventReader<bevy::window::WindowCloseRequested>,
+    mut commands: EventWriter<WindowCommand>,
+) {
+    if closed.iter().any(|event| event.window == window.id) {
+        commands.send(WindowCommand::Hide);
+    }
+}
+
+fn command_system(
+    mut commands: Commands,
+    mut window: ResMut<WindowEntity>,
+    settings: Res<settings::Settings>,
+    mut window_commands: EventReader<WindowCommand>,
+) {
+    for command in window_commands.iter() {
+        let exists = commands.get_entity(window.id).is_some();
+        let command = match command {
+            WindowCommand::Toggle if window.visible && exists => WindowCommand::Hide,
+            WindowCommand::Toggle => WindowCommand::Show,
+            command => *command,
+        };
+
+        match command {
+            WindowCommand::Show if exists => {
+                window.visible = true;
+                window.needs_sync = true;
+            }
+            WindowCommand::Show => *window = WindowEntity::spawn(&mut commands, &settings.window),
+            WindowCommand::Hide => {
+                window.visible = false;
+                window.needs_sync = true;
+            }
+            WindowCommand::Recreate => {
+                if exists {
+                    commands.entity(window.id).despawn();
+                }
+                *window = WindowEntity::spawn(&mut commands, &settings.window);
+            }
+            WindowCommand::Toggle => unreachable!(),
+        }
+    }
+}
+
+/// Applies what winit doesn't get from Bevy, once the winit window exists.
+fn apply_system(
+    mut commands: Commands,
+    mut window: ResMut<WindowEntity>,
+    mut monitors: ResMut<Monitors>,
+    settings: Res<settings::Settings>,
+    theme: Res<theme::CurrentTheme>,
+    windows: NonSend<bevy::winit::WinitWindows>,
+) {
+    if !window.needs_icon && !window.needs_sync && !window.needs_placement && !window.needs_blur {
+        return;
+    }
+    let Some(winit_window) = windows.get_window(window.id) else {
+        return;
+    };
+
+    if window.needs_icon {
+        window.needs_icon = false;
+        winit_window.set_window_icon(Some(icon()));
+    }
+
+    if window.needs_placement {
+        window.needs_placement = false;
+
+        let available = winit_window.available_monitors().collect::<Vec<_>>();
+        monitors.0 = available.iter().enumerate().map(|(index, monitor)| {
+            let size = monitor.size();
+            let name = monitor.name().unwrap_or_else(|| format!("Monitor {}", index + 1));
+            format!("{name} ({}x{})", size.width, size.height)
+        }).collect();
+
+        // Bevy always makes windows fullscreen on the primary monitor.
+        let monitor = settings.window.monitor.and_then(|index| available.get(index));
+        match (settings.window.mode, monitor) {
+            (settings::WindowMode::Borderless, Some(monitor)) => {
+                let fullscreen = winit::window::Fullscreen::Borderless(Some(monitor.clone()));
+                winit_window.set_fullscreen(Some(fullscreen));
+            }
+            (settings::WindowMode::Fullscreen, Some(monitor)) => {
+                // The largest video mode, with the highest refresh rate.
+                let video_mode = monitor.video_modes().max_by_key(|mode| {
+                    let size = mode.size();
+                    (size.width * size.height, mode.refresh_rate_millihertz())
+                });
+                if let Some(video_mode) = video_mode {
+                    winit_window.set_fullscreen(Some(winit::window::Fullscreen::Exclusive(video_mode)));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if window.needs_blur {
+        window.needs_blur = false;
+        if let Err(e) = set_blur(winit_window, theme.0.blur) {
+            warn!("Failed to set the window's blur hint: {e}");
+        }
+    }
+
+    if window.needs_sync {
+        window.needs_sync = false;
+        if !window.visible && is_wayland(winit_window) {
+            // Wayland has no way to hide a window, so it's destroyed instead,
+            // and `command_system` creates it anew once it's shown.
+            commands.entity(window.id).despawn();
+            return;
+        }
+        winit_window.set_visible(window.visible);
+        if window.visible {
+            winit_window.focus_window();
+        }
+    }
+}
+
+/// Applies the theme's background.
+fn theme_system(
+    theme: Res<theme::CurrentTheme>,
+    mut clear_color: ResMut<ClearColor>,
+    mut window: ResMut<WindowEntity>,
+) {
+    if theme.is_changed() {
+        let [r, g, b, a] = theme.0.background_rgba();
+        clear_color.0 = Color::rgba(r, g, b, a);
+        window.needs_blur = true;
+    }
+}
+
+/// Asks the compositor to blur behind the window, which KWin honors on X11.
+/// Other compositors and platforms offer no such hint, or none winit exposes.
+#[cfg(target_os = "linux")]
+fn set_blur(window: &winit::window::Window, blur: bool) -> Result<(), Box<dyn std::error::Error>> {
+    use winit::platform::x11::WindowExtX11;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, PropMode};
+    use x11rb::wrapper::ConnectionExt as _;
+
+    // Not on X11.
+    let Some(xid) = window.xlib_window() else {
+        return Ok(());
+    };
+    let xid = xid as u32;
+
+    let (conn, _) = x11rb::connect(None)?;
+    let atom = conn.intern_atom(false, b"_KDE_NET_WM_BLUR_BEHIND_REGION")?.reply()?.atom;
+    if blur {
+        // Without a region, the whole window is blurred.
+        conn.change_property32(PropMode::REPLACE, xid, atom, AtomEnum::CARDINAL, &[])?;
+    } else {
+        conn.delete_property(xid, atom)?;
+    }
+    conn.flush()?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_blur(_window: &winit::window::Window, _blur: bool) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn is_wayland(window: &winit::window::Window) -> bool {
+    use winit::platform::wayland::WindowExtWayland;
+    window.wayland_surface().is_some()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_wayland(_window: &winit::window::Window) -> bool {
+    false
+}
+
+/// Remembers where the window is, and how large it is, if it is windowed.
+fn geometry_system(
+    window: Res<WindowEntity>,
+    mut settings: ResMut<settings::Settings>,
+    mut moved: EventReader<bevy::window::WindowMoved>,
+    mut resized: EventReader<bevy::window::WindowResized>,
+    mut pending: Local<Option<(settings::WindowSettings, std::time::Instant)>>,
+) {
+    if settings.window.mode != settings::WindowMode::Windowed {
+        moved.clear();
+        resized.clear();
+        return;
+    }
+
+    let mut geometry = pending.as_ref()
+        .map(|(geometry, _)| geometry.clone())
+        .unwrap_or_else(|| settings.window.clone());
+    let mut changed = false;
+    for event in moved.iter().filter(|event| event.entity == window.id) {
+        geometry.position = Some([event.position.x, event.position.y]);
+        changed = true;
+    }
+    for event in resized.iter().filter(|event| event.window == window.id) {
+        geometry.size = Some([event.width, event.height]);
+        changed = true;
+    }
+    if changed {
+        *pending = Some((geometry, std::time::Instant::now()));
+    }
+
+    if let Some((geometry, since)) = pending.as_ref() {
+        if since.elapsed() >= GEOMETRY_SAVE_DELAY {
+            if settings.window.position != geometry.position || settings.window.size != geometry.size {
+                settings.window.position = geometry.position;
+                settings.window.size = geometry.size;
+            }
+            *pending = None;
+        }
+    }
+}